@@ -25,7 +25,17 @@ pub enum AccountCommand {
 }
 
 #[derive(Parser, Clone, Debug)]
-pub struct AccountCloseArgs {}
+pub struct AccountCloseArgs {
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "The proof balance to claim before closing. Required with --blockhash, since the proof can't be fetched offline."
+    )]
+    pub balance: Option<f64>,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
+}
 
 #[derive(Parser, Debug)]
 pub struct BalanceArgs {
@@ -113,16 +123,61 @@ pub struct ClaimArgs {
         long,
         short,
         value_name = "POOL_URL",
-        help = "The optional pool url to claim rewards from."
+        help = "The optional pool url or address to claim rewards from."
     )]
     pub pool_url: Option<String>,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Print the value of an option, e.g. `rpc` or `collect.cores`.")]
+    Get(ConfigGetArgs),
+
+    #[command(about = "Set the default of an option, in the selected profile if any.")]
+    Set(ConfigSetArgs),
+
+    #[command(about = "Print the options in effect for the selected profile.")]
+    Show(ConfigShowArgs),
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct ConfigGetArgs {
+    #[arg(
+        value_name = "KEY",
+        help = "The option, prefixed with its command if any."
+    )]
+    pub key: String,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct ConfigSetArgs {
+    #[arg(
+        value_name = "KEY",
+        help = "The option, prefixed with its command if any."
+    )]
+    pub key: String,
+
+    #[arg(value_name = "VALUE", help = "The default value of the option.")]
+    pub value: String,
 }
 
+#[derive(Parser, Clone, Debug)]
+pub struct ConfigShowArgs {}
+
 #[cfg(feature = "admin")]
 #[derive(Parser, Debug)]
 pub struct InitializeArgs {}
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 pub struct CollectArgs {
     #[arg(
         long,
@@ -163,10 +218,18 @@ pub struct CollectArgs {
         long,
         short,
         value_name = "POOL_URL",
-        help = "The optional pool url to join and forward solutions to."
+        help = "The optional pool url or address to join and forward solutions to."
     )]
     pub pool_url: Option<String>,
 
+    #[arg(
+        long,
+        help = "Collect with a `pool serve-mock` server: skip the on-chain join and time rounds on the local clock.",
+        default_value = "false",
+        requires = "pool_url"
+    )]
+    pub mock_pool: bool,
+
     #[arg(
         long,
         short,
@@ -174,11 +237,387 @@ pub struct CollectArgs {
         default_value = "false"
     )]
     pub verbose: bool,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "Automatically commit pending pool rewards once they exceed this amount of BITZ."
+    )]
+    pub commit_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "Wallet address to claim automatically committed pool rewards to.",
+        requires = "commit_threshold"
+    )]
+    pub claim_to: Option<String>,
+
+    #[arg(
+        long,
+        help = "Verify each pool challenge against the pool's on-chain proof and program config.",
+        default_value = "false",
+        requires = "pool_url"
+    )]
+    pub verify_challenge: bool,
+
+    #[arg(
+        long,
+        value_name = "POOL_URL",
+        help = "Pool url or address to switch to if a challenge fails verification. May be repeated.",
+        requires = "verify_challenge"
+    )]
+    pub fallback_pool: Vec<String>,
+}
+
+/// Options for building a transaction without sending it, e.g. so it can be
+/// signed on an offline machine and broadcast later with `bitz tx submit`.
+#[derive(Parser, Clone, Debug, Default)]
+pub struct OfflineArgs {
+    #[arg(
+        long,
+        conflicts_with = "export_unsigned",
+        help = "Sign the transaction with the local keypairs and write it to a file instead of sending it."
+    )]
+    pub sign_only: bool,
+
+    #[arg(
+        long,
+        help = "Write the unsigned transaction to a file instead of sending it."
+    )]
+    pub export_unsigned: bool,
+
+    #[arg(
+        long,
+        value_name = "BLOCKHASH",
+        help = "The recent blockhash, or with --nonce the stored nonce value, to build the transaction with. Fetched from the RPC if not provided. With a blockhash nothing is fetched, so the transaction can be built on an offline machine."
+    )]
+    pub blockhash: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NONCE_ADDRESS",
+        help = "Durable nonce account to sign against instead of a recent blockhash, so the transaction does not expire."
+    )]
+    pub nonce: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SIGNER",
+        requires = "nonce",
+        help = "The authority of the --nonce account, as a keypair source or, if it signs later, a pubkey. Defaults to the keypair."
+    )]
+    pub nonce_authority: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DECIMALS",
+        help = "Decimals of the mint, for amounts of mints other than BITZ built with --blockhash."
+    )]
+    pub mint_decimals: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        default_value = "transaction.json",
+        help = "Filepath to write the transaction to."
+    )]
+    pub output: String,
+}
+
+impl OfflineArgs {
+    /// Whether the transaction should be written to a file instead of sent.
+    pub fn is_offline(&self) -> bool {
+        self.sign_only || self.export_unsigned
+    }
+
+    /// Whether the transaction is built without the RPC, so that account state
+    /// has to come from arguments.
+    pub fn is_air_gapped(&self) -> bool {
+        self.is_offline() && self.blockhash.is_some()
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct KeyArgs {
+    #[command(subcommand)]
+    pub command: KeyCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum KeyCommand {
+    #[command(about = "Import a secret key into an encrypted keystore.")]
+    Import(KeyImportArgs),
+
+    #[command(about = "Export the secret key of a keypair or keystore in plaintext.")]
+    Export(KeyExportArgs),
+
+    #[command(about = "Encrypt a plaintext keypair file with a passphrase.")]
+    Encrypt(KeyEncryptArgs),
+
+    #[command(about = "Generate a mining keypair from a new mnemonic.")]
+    New(KeyNewArgs),
+
+    #[command(
+        about = "Derive mining keypairs from a mnemonic, e.g. to provision a fleet of miners."
+    )]
+    Derive(KeyDeriveArgs),
+
+    #[command(
+        about = "Serve the keypair as a remote signer, as a stand-in for a signing service."
+    )]
+    Serve(KeyServeArgs),
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyImportArgs {
+    #[arg(
+        value_name = "SECRET_KEY_OR_FILEPATH",
+        help = "Base58 secret key, or a keypair file. Prompts for the secret key if omitted, keeping it out of the shell history."
+    )]
+    pub source: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        value_name = "FILEPATH",
+        default_value = "keystore.json",
+        help = "Filepath to write the keystore to."
+    )]
+    pub output: String,
+
+    #[arg(
+        long,
+        help = "Write a plaintext JSON keypair instead of an encrypted keystore."
+    )]
+    pub plaintext: bool,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyExportArgs {
+    #[arg(
+        value_name = "FILEPATH",
+        help = "The keypair or keystore to export. Defaults to the keypair."
+    )]
+    pub file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "base58",
+        value_parser = ["base58", "json"],
+        help = "Format of the exported secret key."
+    )]
+    pub format: String,
+
+    #[arg(
+        long,
+        short,
+        value_name = "FILEPATH",
+        help = "Filepath to write the secret key to. Defaults to printing it."
+    )]
+    pub output: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyEncryptArgs {
+    #[arg(
+        value_name = "FILEPATH",
+        help = "The keypair file to encrypt. Defaults to the keypair."
+    )]
+    pub file: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        value_name = "FILEPATH",
+        help = "Filepath to write the keystore to. Defaults to replacing the keypair file."
+    )]
+    pub output: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyNewArgs {
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value = "12",
+        help = "Number of words in the mnemonic, 12 or 24."
+    )]
+    pub word_count: usize,
+
+    #[arg(
+        long,
+        short,
+        value_name = "FILEPATH",
+        default_value = "key.txt",
+        help = "Filepath to write the keypair to."
+    )]
+    pub output: String,
+
+    #[command(flatten)]
+    pub derivation: KeyDerivationArgs,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyDeriveArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "COUNT",
+        default_value = "1",
+        help = "Number of keypairs to derive."
+    )]
+    pub count: u32,
+
+    #[arg(
+        long,
+        value_name = "ACCOUNT_INDEX",
+        default_value = "0",
+        help = "Account index of the first keypair to derive."
+    )]
+    pub start: u32,
+
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        default_value = "keys",
+        help = "Directory to write the keypairs and their manifest to."
+    )]
+    pub output_dir: String,
+
+    #[command(flatten)]
+    pub derivation: KeyDerivationArgs,
+}
+
+/// Options shared by the commands that derive keypairs from a mnemonic.
+#[derive(Parser, Clone, Debug)]
+pub struct KeyDerivationArgs {
+    #[arg(
+        long,
+        help = "Prompt for a BIP39 passphrase to derive the keypairs with."
+    )]
+    pub bip39_passphrase: bool,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "json",
+        value_parser = ["base58", "json"],
+        help = "Format of the keypair files."
+    )]
+    pub format: String,
+
+    #[arg(
+        long,
+        conflicts_with = "format",
+        help = "Write encrypted keystores instead of plaintext keypair files."
+    )]
+    pub encrypt: bool,
+
+    #[arg(long, help = "Overwrite existing keypair files.")]
+    pub force: bool,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct KeyServeArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "ADDRESS",
+        help = "The socket address to listen on.",
+        default_value = "127.0.0.1:8787"
+    )]
+    pub listen: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct LookupTableArgs {
+    #[arg(
+        value_name = "LOOKUP_TABLE_ADDRESS",
+        help = "The address of the lookup table to fetch."
+    )]
+    pub address: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<LookupTableCommand>,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum LookupTableCommand {
+    #[command(about = "Create a lookup table holding the accounts your transactions use most.")]
+    Create(LookupTableCreateArgs),
+
+    #[command(about = "Add accounts to a lookup table.")]
+    Extend(LookupTableExtendArgs),
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct LookupTableCreateArgs {
+    #[arg(
+        long,
+        help = "Create the lookup table without adding the default accounts."
+    )]
+    pub empty: bool,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct LookupTableExtendArgs {
+    #[arg(
+        value_name = "LOOKUP_TABLE_ADDRESS",
+        help = "The address of the lookup table to extend."
+    )]
+    pub address: String,
+
+    #[arg(
+        value_name = "ACCOUNT_ADDRESS",
+        help = "Accounts to add. Defaults to the ORE, boost and BITZ accounts of the keypair."
+    )]
+    pub accounts: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct NonceArgs {
+    #[arg(
+        value_name = "NONCE_ADDRESS",
+        help = "The address of the nonce account to fetch. Defaults to the account created with the default seed."
+    )]
+    pub address: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<NonceCommand>,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum NonceCommand {
+    #[command(about = "Create a durable nonce account.")]
+    Create(NonceCreateArgs),
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct NonceCreateArgs {
+    #[arg(
+        long,
+        value_name = "SEED",
+        default_value = "nonce",
+        help = "Seed to derive the nonce account address from the keypair."
+    )]
+    pub seed: String,
+
+    #[arg(
+        long,
+        value_name = "ACCOUNT_ADDRESS",
+        help = "Authority of the nonce account. Defaults to the keypair."
+    )]
+    pub authority: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 pub struct PoolArgs {
-    #[arg(value_name = "POOL_URL", help = "The pool url to connect to.")]
+    #[arg(
+        value_name = "POOL_URL",
+        help = "The pool url or address to connect to."
+    )]
     pub pool_url: Option<String>,
 
     #[command(subcommand)]
@@ -189,11 +628,239 @@ pub struct PoolArgs {
 pub enum PoolCommand {
     #[command(about = "Commit a pending pool balance to the chain.")]
     Commit(PoolCommitArgs),
+
+    #[command(about = "Watch pending pool rewards and commit them once they exceed a threshold.")]
+    Watch(PoolWatchArgs),
+
+    #[command(
+        about = "Commit and claim your whole pool balance. The member account stays open and its rent is not refunded."
+    )]
+    Leave(PoolLeaveArgs),
+
+    #[command(
+        about = "Stake boost tokens in the global boost for their mint. Pool staking has moved to the global boost program."
+    )]
+    Stake(PoolStakeArgs),
+
+    #[command(about = "Unstake boost tokens from the pool.")]
+    Unstake(PoolUnstakeArgs),
+
+    #[command(about = "List your share accounts in the pool.")]
+    Shares(PoolSharesArgs),
+
+    #[command(about = "Run a local mock pool server for testing pool collecting.")]
+    ServeMock(PoolServeMockArgs),
+
+    #[command(about = "Operate your own pool for your machines.")]
+    Operate(PoolOperateArgs),
 }
 
 #[derive(Parser, Clone, Debug)]
 pub struct PoolCommitArgs {}
 
+#[derive(Parser, Clone, Debug)]
+pub struct PoolLeaveArgs {
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "Wallet address to receive claimed tokens."
+    )]
+    pub to: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolStakeArgs {
+    #[arg(
+        value_name = "AMOUNT",
+        help = "The amount of stake to deposit. Defaults to max."
+    )]
+    pub amount: Option<f64>,
+
+    #[arg(
+        long,
+        short,
+        value_name = "MINT_ADDRESS",
+        help = "The mint to stake. Defaults to BITZ mint when not provided."
+    )]
+    pub mint: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Token account to deposit from. Defaults to the associated token account."
+    )]
+    pub token_account: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolUnstakeArgs {
+    #[arg(
+        value_name = "AMOUNT",
+        help = "The amount of the token to unstake. Defaults to max."
+    )]
+    pub amount: Option<f64>,
+
+    #[arg(
+        long,
+        short,
+        value_name = "MINT_ADDRESS",
+        help = "The mint to unstake. Defaults to BITZ mint when not provided."
+    )]
+    pub mint: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN_ACCOUNT_ADDRESS",
+        help = "Token account to receive unstaked funds. Defaults to the associated token account."
+    )]
+    pub token_account: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolSharesArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "ACCOUNT_ADDRESS",
+        help = "List the share accounts of another authority."
+    )]
+    pub authority: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolOperateArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "ADDRESS",
+        help = "The socket address to listen on.",
+        default_value = "0.0.0.0:3000"
+    )]
+    pub listen: String,
+
+    #[arg(
+        long,
+        value_name = "POOL_URL",
+        help = "The public url of the pool. Required to launch a new pool."
+    )]
+    pub url: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        value_name = "MIN_DIFFICULTY",
+        help = "The minimum difficulty to accept contributions at.",
+        default_value = "0"
+    )]
+    pub min_difficulty: u64,
+
+    #[arg(
+        long,
+        short,
+        value_name = "SECONDS",
+        help = "The number seconds before the deadline to stop accepting contributions and submit.",
+        default_value = "5"
+    )]
+    pub buffer_time: u64,
+
+    #[arg(
+        long,
+        value_name = "DEVICES",
+        help = "The number of devices allowed per member.",
+        default_value = "5"
+    )]
+    pub num_devices: u8,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "The percentage of each reward kept by the operator.",
+        default_value = "0"
+    )]
+    pub operator_fee: u64,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "The number of rounds between on-chain balance commits for all members.",
+        default_value = "10"
+    )]
+    pub attribute_interval: u64,
+
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        help = "Filepath to persist member balances to.",
+        default_value = "pool-members.json"
+    )]
+    pub state: String,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolServeMockArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "ADDRESS",
+        help = "The socket address to listen on.",
+        default_value = "127.0.0.1:3000"
+    )]
+    pub listen: String,
+
+    #[arg(
+        long,
+        short,
+        value_name = "MIN_DIFFICULTY",
+        help = "The minimum difficulty to hand out with each challenge.",
+        default_value = "8"
+    )]
+    pub min_difficulty: u64,
+
+    #[arg(
+        long,
+        value_name = "DEVICES",
+        help = "The number of devices allowed per member.",
+        default_value = "5"
+    )]
+    pub num_devices: u8,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "The number of seconds between new challenges.",
+        default_value = "60"
+    )]
+    pub challenge_interval: u64,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct PoolWatchArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "AMOUNT",
+        help = "The amount of pending BITZ rewards above which to commit.",
+        default_value = "1"
+    )]
+    pub threshold: f64,
+
+    #[arg(
+        long,
+        short,
+        value_name = "SECONDS",
+        help = "The number of seconds to wait between balance checks.",
+        default_value = "300"
+    )]
+    pub interval: u64,
+
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "Wallet address to claim committed rewards to."
+    )]
+    pub claim_to: Option<String>,
+}
+
 #[derive(Parser, Debug)]
 pub struct ProgramArgs {}
 
@@ -252,6 +919,9 @@ pub struct StakeClaimArgs {
         help = "Wallet address to receive claimed tokens."
     )]
     pub to: Option<String>,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -268,6 +938,15 @@ pub struct StakeDepositArgs {
         help = "Token account to deposit from. Defaults to the associated token account."
     )]
     pub token_account: Option<String>,
+
+    #[arg(
+        long,
+        help = "Open the stake account in the same transaction. With --blockhash the account can't be checked, so pass this if it doesn't exist yet."
+    )]
+    pub open_stake: bool,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -284,6 +963,9 @@ pub struct StakeWithdrawArgs {
         help = "Token account to withdraw to. Defaults to the associated token account."
     )]
     pub token_account: Option<String>,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -308,33 +990,56 @@ pub struct TransferArgs {
         help = "The account address of the receipient."
     )]
     pub to: String,
+
+    #[command(flatten)]
+    pub offline: OfflineArgs,
 }
 
 #[derive(Parser, Debug)]
-pub struct UnstakeArgs {
+pub struct TxArgs {
+    #[command(subcommand)]
+    pub command: TxCommand,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TxCommand {
+    #[command(about = "Sign a transaction file with the local keypairs, without broadcasting it.")]
+    Sign(TxSignArgs),
+
+    #[command(about = "Combine the signatures of offline transactions and broadcast them.")]
+    Submit(TxSubmitArgs),
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct TxSignArgs {
     #[arg(
-        value_name = "AMOUNT",
-        help = "The amount of the token to unstake. Defaults to max."
+        value_name = "FILEPATH",
+        help = "Transaction file written by --export-unsigned or --sign-only."
     )]
-    pub amount: Option<f64>,
-
-    #[arg(value_name = "MINT_ADDRESS", help = "The mint to unstake.")]
-    pub mint: String,
+    pub file: String,
 
     #[arg(
         long,
-        value_name = "TOKEN_ACCOUNT_ADDRESS",
-        help = "Token account to receive unstaked funds. Defaults to the associated token account."
+        value_name = "FILEPATH",
+        help = "File to write the signed transaction to. Defaults to overwriting the input file."
     )]
-    pub token_account: Option<String>,
+    pub output: Option<String>,
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct TxSubmitArgs {
+    #[arg(
+        value_name = "FILEPATH",
+        required = true,
+        help = "Transaction files written by --sign-only or --export-unsigned. Signatures from all files are combined."
+    )]
+    pub files: Vec<String>,
 
     #[arg(
         long,
-        short,
-        value_name = "POOL_URL",
-        help = "The optional pool url to unstake from."
+        help = "Do not sign with the local keypairs, even if their signatures are missing."
     )]
-    pub pool_url: Option<String>,
+    pub no_sign: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -14,10 +14,11 @@ use tabled::{
 };
 
 use crate::{
-    args::{AccountArgs, AccountCloseArgs, AccountCommand, ClaimArgs},
+    args::{AccountArgs, AccountCloseArgs, AccountCommand, ClaimArgs, OfflineArgs},
+    send::required_offline,
     utils::{
-        amount_u64_to_f64, ask_confirm, format_timestamp, get_proof, get_proof_with_authority,
-        ComputeBudget, TableData, TableSectionTitle,
+        amount_f64_to_u64, amount_u64_to_f64, ask_confirm, format_timestamp, get_proof,
+        get_proof_with_authority, ComputeBudget, TableData, TableSectionTitle,
     },
     Miner,
 };
@@ -192,32 +193,64 @@ impl Miner {
         }
     }
 
-    async fn close(&self, _args: AccountCloseArgs) {
-        // Confirm proof exists
+    async fn close(&self, args: AccountCloseArgs) {
+        // Confirm proof exists, or take its balance from the args when building offline
         let signer = self.signer();
-        let proof = get_proof_with_authority(&self.rpc_client, signer.pubkey())
-            .await
-            .expect("Failed to fetch proof account");
+        let balance = if args.offline.is_air_gapped() {
+            match args.balance {
+                Some(balance) => amount_f64_to_u64(balance),
+                None => {
+                    println!("{:?}", required_offline("--balance"));
+                    return;
+                }
+            }
+        } else {
+            get_proof_with_authority(&self.rpc_client, signer.pubkey())
+                .await
+                .expect("Failed to fetch proof account")
+                .balance
+        };
 
         // Confirm the user wants to close.
         if !ask_confirm(
             format!("{} You have {} BITZ staked in this account.\nAre you sure you want to {}close this account? [Y/n]", 
                 "WARNING:".bold().yellow(),
-                amount_to_ui_amount(proof.balance, eore_api::consts::TOKEN_DECIMALS),
-                if proof.balance.gt(&0) { "claim your stake and "} else { "" }
+                amount_to_ui_amount(balance, eore_api::consts::TOKEN_DECIMALS),
+                if balance.gt(&0) { "claim your stake and "} else { "" }
             ).as_str()
         ) {
             return;
         }
 
+        // Claim stake and close in a single offline transaction
+        if args.offline.is_offline() {
+            let mut ixs = vec![];
+            if balance.gt(&0) {
+                let (beneficiary, ix) =
+                    self.create_ata_ix(signer.pubkey(), eore_api::consts::MINT_ADDRESS);
+                ixs.push(ix);
+                ixs.push(eore_api::sdk::claim(signer.pubkey(), beneficiary, balance));
+            }
+            ixs.push(eore_api::sdk::close(signer.pubkey()));
+            if let Err(err) = self
+                .export_transaction(&ixs, ComputeBudget::Dynamic, &args.offline)
+                .await
+            {
+                println!("{:?}", err);
+            }
+            return;
+        }
+
         // Claim stake
-        if proof.balance.gt(&0) {
+        if balance.gt(&0) {
             self.claim_from_proof(ClaimArgs {
                 amount: None,
                 to: None,
                 pool_url: None,
+                offline: OfflineArgs::default(),
             })
-            .await;
+            .await
+            .ok();
         }
 
         // Submit close transaction
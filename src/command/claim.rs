@@ -2,12 +2,13 @@ use std::str::FromStr;
 
 use colored::*;
 use eore_api::consts::MINT_ADDRESS;
-use solana_program::pubkey::Pubkey;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
 use solana_sdk::signature::{Signature, Signer};
 use spl_token::amount_to_ui_amount;
 
 use crate::{
     args::ClaimArgs,
+    send::required_offline,
     utils::{amount_f64_to_u64, ask_confirm, get_proof_with_authority, ComputeBudget},
     Miner,
 };
@@ -17,28 +18,45 @@ use super::pool::Pool;
 impl Miner {
     pub async fn claim(&self, args: ClaimArgs) -> Result<(), crate::error::Error> {
         match args.pool_url {
+            Some(_) if args.offline.is_air_gapped() => Err(crate::error::Error::Internal(
+                "pool claims need the pool server, export the transaction with --export-unsigned and sign it offline with `bitz tx sign`".to_string(),
+            )),
             Some(ref pool_url) => {
-                let pool = &Pool {
-                    http_client: reqwest::Client::new(),
-                    pool_url: pool_url.clone(),
-                };
+                let pool = &Pool::connect(self, pool_url).await?;
                 let _ = self.claim_from_pool(args, pool).await?;
                 Ok(())
             }
-            None => {
-                self.claim_from_proof(args).await;
-                Ok(())
-            }
+            None => self.claim_from_proof(args).await,
         }
     }
 
-    pub async fn claim_from_proof(&self, args: ClaimArgs) {
+    pub async fn claim_from_proof(&self, args: ClaimArgs) -> Result<(), crate::error::Error> {
         let signer = self.signer();
         let pubkey = signer.pubkey();
-        let proof = get_proof_with_authority(&self.rpc_client, pubkey).await.expect("Failed to fetch proof account");
+        let proof = if args.offline.is_air_gapped() {
+            None
+        } else {
+            let proof = get_proof_with_authority(&self.rpc_client, pubkey)
+                .await
+                .map_err(|err| {
+                    crate::error::Error::Internal(format!("Failed to fetch proof account: {}", err))
+                })?;
+            Some(proof)
+        };
         let mut ixs = vec![];
         let beneficiary = match args.to {
+            None if args.offline.is_offline() => {
+                let (ata, ix) = self.create_ata_ix(pubkey, MINT_ADDRESS);
+                ixs.push(ix);
+                ata
+            }
             None => self.initialize_ata(pubkey).await,
+            Some(to) if args.offline.is_offline() => {
+                let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
+                let (ata, ix) = self.create_ata_ix(wallet, MINT_ADDRESS);
+                ixs.push(ix);
+                ata
+            }
             Some(to) => {
                 // Create beneficiary token account, if needed
                 let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
@@ -66,10 +84,10 @@ impl Miner {
         };
 
         // Parse amount to claim
-        let amount = if let Some(amount) = args.amount {
-            amount_f64_to_u64(amount)
-        } else {
-            proof.balance
+        let amount = match (args.amount, proof) {
+            (Some(amount), _) => amount_f64_to_u64(amount),
+            (None, Some(proof)) => proof.balance,
+            (None, None) => return Err(required_offline("the amount to claim")),
         };
 
         // Confirm user wants to claim
@@ -84,14 +102,20 @@ impl Miner {
             )
             .as_str(),
         ) {
-            return;
+            return Ok(());
         }
 
         // Send and confirm
         ixs.push(eore_api::sdk::claim(pubkey, beneficiary, amount));
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(32_000), false)
+        if args.offline.is_offline() {
+            return self
+                .export_transaction(&ixs, ComputeBudget::Dynamic, &args.offline)
+                .await;
+        }
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
             .await
             .ok();
+        Ok(())
     }
 
     async fn claim_from_pool(
@@ -107,6 +131,11 @@ impl Miner {
 
         // Create beneficiary token account, if needed
         let beneficiary = match args.to {
+            None if args.offline.is_offline() => {
+                let (ata, ix) = self.create_ata_ix(self.signer().pubkey(), MINT_ADDRESS);
+                ixs.push(ix);
+                ata
+            }
             None => self.initialize_ata(self.signer().pubkey()).await,
             Some(to) => {
                 let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
@@ -162,9 +191,63 @@ impl Miner {
             pool_address.address,
             amount,
         ));
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(50_000), false)
+        if args.offline.is_offline() {
+            self.export_transaction(&ixs, ComputeBudget::Dynamic, &args.offline)
+                .await?;
+            return Ok(Signature::default());
+        }
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false).await
+    }
+
+    /// Claims committed pool rewards to a wallet without prompting, creating its
+    /// token account if needed. Used for unattended commits.
+    pub async fn claim_from_pool_to(
+        &self,
+        pool_address: Pubkey,
+        wallet: Pubkey,
+        amount: u64,
+    ) -> Result<Signature, crate::error::Error> {
+        let pubkey = self.signer().pubkey();
+        let mut ixs = vec![];
+        let beneficiary =
+            spl_associated_token_account::get_associated_token_address(&wallet, &MINT_ADDRESS);
+        if self
+            .rpc_client
+            .get_token_account(&beneficiary)
             .await
-            .map_err(From::from)
+            .is_err()
+        {
+            ixs.push(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &pubkey,
+                    &wallet,
+                    &eore_api::consts::MINT_ADDRESS,
+                    &spl_token::id(),
+                ),
+            );
+        }
+        ixs.push(ore_pool_api::sdk::claim(
+            pubkey,
+            beneficiary,
+            pool_address,
+            amount,
+        ));
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false).await
+    }
+
+    /// Returns the wallet's token account and an instruction creating it unless it
+    /// exists. Offline transactions use this in place of `initialize_ata`, as they
+    /// can't send a separate transaction first.
+    pub fn create_ata_ix(&self, wallet: Pubkey, mint: Pubkey) -> (Pubkey, Instruction) {
+        let token_account_pubkey =
+            spl_associated_token_account::get_associated_token_address(&wallet, &mint);
+        let ix = spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &self.signer().pubkey(),
+            &wallet,
+            &mint,
+            &spl_token::id(),
+        );
+        (token_account_pubkey, ix)
     }
 
     pub async fn initialize_ata(&self, wallet: Pubkey) -> Pubkey {
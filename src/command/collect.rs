@@ -1,7 +1,7 @@
 use std::{
     io::stdout,
+    str::FromStr,
     sync::{Arc, RwLock},
-    thread::sleep,
     time::{Duration, Instant},
     usize,
 };
@@ -18,15 +18,19 @@ use drillx::{
     Hash, Solution,
 };
 use eore_api::{
-    consts::{BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION},
+    consts::{BUS_ADDRESSES, BUS_COUNT, CONFIG_ADDRESS, EPOCH_DURATION},
     event::MineEvent,
     state::{proof_pda, Bus, Config},
 };
+use ore_pool_types::Challenge;
 use rand::Rng;
 use solana_program::pubkey::Pubkey;
 use solana_rpc_client::spinner;
 use solana_sdk::{signature::Signature, signer::Signer};
-use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    UiTransactionEncoding,
+};
 use steel::AccountDeserialize;
 use tabled::{
     settings::{
@@ -39,25 +43,50 @@ use tabled::{
 
 use crate::{
     args::CollectArgs,
-    error::Error,
+    error::{Error, PoolError},
     utils::{
-        amount_u64_to_f64, format_duration, format_timestamp, get_clock, get_config,
-        get_updated_proof_with_authority, ComputeBudget, PoolCollectingData, SoloCollectingData,
+        amount_f64_to_u64, amount_u64_to_f64, format_duration, format_timestamp, get_clock,
+        get_config, get_proof, get_updated_proof_with_authority, ComputeBudget, PoolCollectingData,
+        SoloCollectingData,
     },
     Miner,
 };
 
-use super::pool::Pool;
+use super::{
+    pool::{nonce_range, Pool},
+    pool_server::unix_timestamp,
+};
+
+/// Attempts to wait for the rpc to observe the proof update behind a pool challenge.
+const CHALLENGE_VERIFY_RETRIES: usize = 5;
+
+/// Rows kept in the collecting tables.
+const COLLECTING_TABLE_ROWS: usize = 11;
 
 impl Miner {
     pub async fn collect(&self, args: CollectArgs) -> Result<(), Error> {
         match args.pool_url {
             Some(ref pool_url) => {
-                let pool = &Pool {
-                    http_client: reqwest::Client::new(),
-                    pool_url: pool_url.clone(),
-                };
-                self.collect_pool(args, pool).await?;
+                // Switch to the next fallback pool whenever a pool serves a bad challenge
+                let pool_urls = std::iter::once(pool_url).chain(args.fallback_pool.iter());
+                let num_pools = args.fallback_pool.len() + 1;
+                for (i, pool_url) in pool_urls.enumerate() {
+                    let mut pool = Pool::connect(self, pool_url).await?;
+                    if args.mock_pool {
+                        pool = pool.into_mock().await?;
+                    }
+                    match self.collect_pool(args.clone(), &pool).await {
+                        Err(Error::PoolChallengeMismatch(reason)) if i + 1 < num_pools => {
+                            println!(
+                                "{} Leaving pool {}: {}",
+                                "WARNING".bold().yellow(),
+                                pool_url,
+                                reason
+                            );
+                        }
+                        result => return result,
+                    }
+                }
             }
             None => {
                 self.collect_solo(args).await;
@@ -114,7 +143,10 @@ impl Miner {
             last_hash_at = proof.last_hash_at;
 
             // Calculate cutoff time
-            let cutoff_time = self.get_cutoff(proof.last_hash_at, args.buffer_time).await;
+            let cutoff_time = self
+                .get_cutoff(proof.last_hash_at, args.buffer_time)
+                .await
+                .expect("Failed to fetch clock account");
 
             // Build nonce indices
             let mut nonce_indices = Vec::with_capacity(cores as usize);
@@ -136,13 +168,11 @@ impl Miner {
 
             // Build instruction set
             let mut ixs = vec![eore_api::sdk::auth(proof_pda(signer.pubkey()).0)];
-            let mut compute_budget = 750_000;
 
             // Check for reset
             if self.should_reset(config).await
             // && rand::thread_rng().gen_range(0..100).eq(&0)
             {
-                compute_budget += 100_000;
                 ixs.push(eore_api::sdk::reset(signer.pubkey()));
             }
 
@@ -158,23 +188,31 @@ impl Miner {
 
             // Submit transaction
             match self
-                .send_and_confirm(&ixs, ComputeBudget::Fixed(compute_budget), false)
+                .send_and_confirm_mining(&ixs, ComputeBudget::Dynamic, false)
                 .await
             {
-                Ok(sig) => self.fetch_solo_collect_event(sig, verbose).await,
+                Ok(sig) => {
+                    // Fetch the event in the background, so hashing resumes right away
+                    let miner = self.clone();
+                    tokio::spawn(async move {
+                        miner.fetch_solo_collect_event(sig, verbose).await;
+                    });
+                }
                 Err(err) => {
-                    let collecting_data = SoloCollectingData::failed();
-                    let mut data = self.solo_collecting_data.write().unwrap();
-                    if !data.is_empty() {
-                        data.remove(0);
-                    }
-                    data.insert(0, collecting_data);
-                    drop(data);
+                    self.push_solo_collecting_row(SoloCollectingData::failed());
 
                     // Log collecting table
                     self.update_solo_collecting_table(verbose);
                     println!("{}: {}", "ERROR".bold().red(), err);
 
+                    // Timeouts and retryable errors only mean this solution didn't land,
+                    // so keep collecting
+                    match err {
+                        Error::TransactionTimeout(..) => continue,
+                        Error::Program(err) if err.is_retryable() => continue,
+                        _ => {}
+                    }
+
                     return;
                 }
             }
@@ -182,9 +220,17 @@ impl Miner {
     }
 
     async fn collect_pool(&self, args: CollectArgs, pool: &Pool) -> Result<(), Error> {
+        // Parse auto commit settings
+        let commit_threshold = args.commit_threshold.map(amount_f64_to_u64);
+        let claim_to = match args.claim_to {
+            Some(ref to) => Some(Pubkey::from_str(to)?),
+            None => None,
+        };
+
         // Register, if needed
         let pool_member = pool.post_pool_register(self).await?;
-        let nonce_index = pool_member.id as u64;
+        let mut nonce_index = pool_member.id as u64;
+        let pool_address = pool.get_pool_address().await?.address;
 
         // Get device id
         let device_id = args.device_id.unwrap_or(0);
@@ -204,7 +250,7 @@ impl Miner {
             async move {
                 while let Some(solution) = rx.recv().await {
                     if let Err(err) = pool.post_pool_solution(&miner, &solution).await {
-                        println!("error submitting solution: {:?}", err);
+                        println!("error submitting solution: {}", err);
                     }
                 }
             }
@@ -212,16 +258,49 @@ impl Miner {
 
         // Start collecting loop
         let mut last_hash_at = 0;
+        let mut commit_task: Option<tokio::task::JoinHandle<()>> = None;
         loop {
             // Fetch latest challenge
             let member_challenge = match pool.get_updated_pool_challenge(self, last_hash_at).await {
-                Err(_err) => {
+                Err(Error::Pool(PoolError::MemberNotFound)) => {
+                    // Pool server lost our registration (e.g. restarted), register again
+                    println!(
+                        "{} Member not found, re-registering",
+                        "WARNING".bold().yellow()
+                    );
+                    match pool.post_pool_register(self).await {
+                        Ok(member) => nonce_index = member.id as u64,
+                        Err(_err) => {
+                            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                        }
+                    }
+                    continue;
+                }
+                Err(err) => {
+                    println!("{} {}", "ERROR".bold().red(), err);
                     tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
                     continue;
                 }
                 Ok(member_challenge) => member_challenge,
             };
 
+            // Check the pool isn't handing out a stale or forged challenge
+            if args.verify_challenge {
+                if let Err(reason) = self
+                    .verify_pool_challenge(pool_address, &member_challenge.challenge)
+                    .await
+                {
+                    if !args.fallback_pool.is_empty() {
+                        return Err(Error::PoolChallengeMismatch(reason));
+                    }
+                    println!(
+                        "{} Pool challenge failed verification: {}",
+                        "WARNING".bold().yellow(),
+                        reason
+                    );
+                }
+            }
+
             // Log collecting table
             self.update_pool_collecting_table(verbose);
 
@@ -229,13 +308,18 @@ impl Miner {
             last_hash_at = member_challenge.challenge.lash_hash_at;
 
             // Compute cutoff time
-            let cutoff_time = self.get_cutoff(last_hash_at, args.buffer_time).await;
+            // Mock pools run on local time, since their challenges aren't on-chain
+            let cutoff_time = if pool.mock {
+                cutoff_at(last_hash_at, args.buffer_time, unix_timestamp())
+            } else {
+                self.get_cutoff(last_hash_at, args.buffer_time).await?
+            };
 
             // Build nonce indices
-            let num_total_members = member_challenge.num_total_members.max(1);
-            let member_search_space_size = u64::MAX.saturating_div(num_total_members);
+            let (member_start, member_end) =
+                nonce_range(nonce_index, member_challenge.num_total_members);
             let device_search_space_size =
-                member_search_space_size.saturating_div(member_challenge.num_devices as u64);
+                (member_end - member_start).saturating_div(member_challenge.num_devices as u64);
 
             // Check device id doesn't go beyond pool limit
             if (device_id as u8) > member_challenge.num_devices {
@@ -243,8 +327,7 @@ impl Miner {
             }
 
             // Calculate bounds on nonce space
-            let left_bound = member_search_space_size.saturating_mul(nonce_index)
-                + device_id.saturating_mul(device_search_space_size);
+            let left_bound = member_start + device_id.saturating_mul(device_search_space_size);
 
             // Split nonce-device space for muliple cores
             let range_per_core = device_search_space_size.saturating_div(cores);
@@ -276,7 +359,89 @@ impl Miner {
                         .await;
                 }
             }
+
+            // Commit pending rewards in the background, if over threshold
+            if let Some(threshold) = commit_threshold {
+                let idle = match commit_task {
+                    Some(ref task) => task.is_finished(),
+                    None => true,
+                };
+                if idle {
+                    let miner = self.clone();
+                    let pool = pool.clone();
+                    commit_task = Some(tokio::spawn(async move {
+                        if let Err(err) = miner
+                            .commit_pending_rewards(&pool, threshold, claim_to)
+                            .await
+                        {
+                            println!("{} {:?}", "ERROR".bold().red(), err);
+                        }
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Checks a pool challenge matches the pool's on-chain proof and asks for at
+    /// least the program's min difficulty. Returns the reason on mismatch.
+    async fn verify_pool_challenge(
+        &self,
+        pool_address: Pubkey,
+        challenge: &Challenge,
+    ) -> Result<(), String> {
+        // Fetch config
+        let data = match self.rpc_client.get_account_data(&CONFIG_ADDRESS).await {
+            Ok(data) => data,
+            Err(err) => {
+                println!(
+                    "{} Could not fetch config to verify challenge: {}",
+                    "WARNING".bold().yellow(),
+                    err
+                );
+                return Ok(());
+            }
+        };
+        let config = Config::try_from_bytes(&data).map_err(|err| err.to_string())?;
+        if challenge.min_difficulty < config.min_difficulty {
+            return Err(format!(
+                "min difficulty {} is below the program minimum {}",
+                challenge.min_difficulty, config.min_difficulty
+            ));
+        }
+
+        // Fetch pool proof, allowing the rpc to catch up with the pool server
+        let proof_address = proof_pda(pool_address).0;
+        for _ in 0..CHALLENGE_VERIFY_RETRIES {
+            let proof = match get_proof(&self.rpc_client, proof_address).await {
+                Ok(proof) => proof,
+                Err(err) => {
+                    println!(
+                        "{} Could not fetch pool proof to verify challenge: {}",
+                        "WARNING".bold().yellow(),
+                        err
+                    );
+                    return Ok(());
+                }
+            };
+            if proof.last_hash_at < challenge.lash_hash_at {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                continue;
+            }
+            if proof.last_hash_at > challenge.lash_hash_at {
+                return Err(format!(
+                    "challenge from {} is stale, on-chain proof was updated at {}",
+                    challenge.lash_hash_at, proof.last_hash_at
+                ));
+            }
+            if proof.challenge != challenge.challenge {
+                return Err("challenge does not match the pool's on-chain proof".to_string());
+            }
+            return Ok(());
         }
+        Err(format!(
+            "no on-chain proof update matches the challenge from {}",
+            challenge.lash_hash_at
+        ))
     }
 
     async fn find_hash_par(
@@ -424,7 +589,7 @@ impl Miner {
         }
     }
 
-    async fn should_reset(&self, config: Config) -> bool {
+    pub async fn should_reset(&self, config: Config) -> bool {
         let clock = get_clock(&self.rpc_client)
             .await
             .expect("Failed to fetch clock account");
@@ -435,18 +600,14 @@ impl Miner {
             .le(&clock.unix_timestamp)
     }
 
-    async fn get_cutoff(&self, last_hash_at: i64, buffer_time: u64) -> u64 {
+    pub async fn get_cutoff(&self, last_hash_at: i64, buffer_time: u64) -> Result<u64, Error> {
         let clock = get_clock(&self.rpc_client)
             .await
-            .expect("Failed to fetch clock account");
-        last_hash_at
-            .saturating_add(60)
-            .saturating_sub(buffer_time as i64)
-            .saturating_sub(clock.unix_timestamp)
-            .max(0) as u64
+            .map_err(|err| Error::Internal(format!("Failed to fetch clock account: {}", err)))?;
+        Ok(cutoff_at(last_hash_at, buffer_time, clock.unix_timestamp))
     }
 
-    async fn find_bus(&self) -> Pubkey {
+    pub async fn find_bus(&self) -> Pubkey {
         // Fetch the bus with the largest balance
         if let Ok(accounts) = self.rpc_client.get_multiple_accounts(&BUS_ADDRESSES).await {
             let mut top_bus_balance: u64 = 0;
@@ -471,13 +632,7 @@ impl Miner {
 
     async fn fetch_solo_collect_event(&self, sig: Signature, verbose: bool) {
         // Add loading row
-        let collecting_data = SoloCollectingData::fetching(sig);
-        let mut data = self.solo_collecting_data.write().unwrap();
-        data.insert(0, collecting_data);
-        if !data.is_empty() {
-            data.remove(0);
-        }
-        drop(data);
+        self.push_solo_collecting_row(SoloCollectingData::fetching(sig));
 
         // Update table
         self.update_solo_collecting_table(verbose);
@@ -493,7 +648,7 @@ impl Miner {
             if tx.is_ok() {
                 break;
             }
-            sleep(Duration::from_secs(1));
+            tokio::time::sleep(Duration::from_secs(1)).await;
             attempts += 1;
             if attempts > 30 {
                 break;
@@ -501,58 +656,30 @@ impl Miner {
         }
 
         // Parse transaction response
-        if let Ok(tx) = tx {
-            if let Some(meta) = tx.transaction.meta {
-                if let OptionSerializer::Some(log_messages) = meta.log_messages {
-                    if let Some(return_log) = log_messages
-                        .iter()
-                        .find(|log| log.starts_with("Program return: "))
-                    {
-                        if let Some(return_data) =
-                            return_log.strip_prefix(&format!("Program return: {} ", eore_api::ID))
-                        {
-                            if let Ok(return_data) = return_data.from_base64() {
-                                let mut data = self.solo_collecting_data.write().unwrap();
-                                let event = MineEvent::from_bytes(&return_data);
-                                let collecting_data = SoloCollectingData {
-                                    signature: if verbose {
-                                        sig.to_string()
-                                    } else {
-                                        format!("{}...", sig.to_string()[..8].to_string())
-                                    },
-                                    block: tx.slot.to_string(),
-                                    timestamp: format_timestamp(tx.block_time.unwrap_or_default()),
-                                    difficulty: event.difficulty.to_string(),
-                                    base_reward: if event.net_base_reward > 0 {
-                                        format!("{:#.11}", amount_u64_to_f64(event.net_base_reward))
-                                    } else {
-                                        "0".to_string()
-                                    },
-                                    boost_reward: if event.net_miner_boost_reward > 0 {
-                                        format!(
-                                            "{:#.11}",
-                                            amount_u64_to_f64(event.net_miner_boost_reward)
-                                        )
-                                    } else {
-                                        "0".to_string()
-                                    },
-                                    total_reward: if event.net_reward > 0 {
-                                        format!("{:#.11}", amount_u64_to_f64(event.net_reward))
-                                    } else {
-                                        "0".to_string()
-                                    },
-                                    timing: format!("{}s", event.timing),
-                                    status: "Confirmed".bold().green().to_string(),
-                                };
-                                data.insert(0, collecting_data);
-                            }
-                        }
-                    }
-                }
+        let collecting_data = tx
+            .ok()
+            .and_then(|tx| parse_solo_collect_event(sig, tx, verbose))
+            .unwrap_or_else(|| SoloCollectingData::unfetched(sig));
+
+        // Replace the loading row, which may have moved down
+        let fetching = sig.to_string();
+        let mut data = self.solo_collecting_data.write().unwrap();
+        match data.iter().position(|row| row.signature == fetching) {
+            Some(index) => data[index] = collecting_data,
+            None => {
+                data.insert(0, collecting_data);
+                data.truncate(COLLECTING_TABLE_ROWS);
             }
         }
     }
 
+    /// Adds a row to the top of the solo collecting table, dropping the oldest.
+    fn push_solo_collecting_row(&self, collecting_data: SoloCollectingData) {
+        let mut data = self.solo_collecting_data.write().unwrap();
+        data.insert(0, collecting_data);
+        data.truncate(COLLECTING_TABLE_ROWS);
+    }
+
     async fn fetch_pool_collect_event(&self, pool: &Pool, last_hash_at: i64, verbose: bool) {
         let collecting_data = match pool
             .get_latest_pool_event(self.signer().pubkey(), last_hash_at)
@@ -607,10 +734,7 @@ impl Miner {
         // Add row
         let mut data = self.pool_collecting_data.write().unwrap();
         data.insert(0, collecting_data);
-        if data.len() >= 12 {
-            data.pop();
-        }
-        drop(data);
+        data.truncate(COLLECTING_TABLE_ROWS);
     }
 
     fn update_solo_collecting_table(&self, verbose: bool) {
@@ -670,3 +794,57 @@ impl Miner {
         }
     }
 }
+
+/// Seconds left to hash before submitting, for a challenge issued at
+/// `last_hash_at`.
+fn cutoff_at(last_hash_at: i64, buffer_time: u64, now: i64) -> u64 {
+    last_hash_at
+        .saturating_add(60)
+        .saturating_sub(buffer_time as i64)
+        .saturating_sub(now)
+        .max(0) as u64
+}
+
+/// Reads the mine event from the return data of a landed collect transaction.
+fn parse_solo_collect_event(
+    sig: Signature,
+    tx: EncodedConfirmedTransactionWithStatusMeta,
+    verbose: bool,
+) -> Option<SoloCollectingData> {
+    let OptionSerializer::Some(log_messages) = tx.transaction.meta?.log_messages else {
+        return None;
+    };
+    let return_data = log_messages.iter().find_map(|log| {
+        log.strip_prefix(&format!("Program return: {} ", eore_api::ID))
+            .map(str::to_string)
+    })?;
+    let return_data = return_data.from_base64().ok()?;
+    let event = MineEvent::from_bytes(&return_data);
+    Some(SoloCollectingData {
+        signature: if verbose {
+            sig.to_string()
+        } else {
+            format!("{}...", &sig.to_string()[..8])
+        },
+        block: tx.slot.to_string(),
+        timestamp: format_timestamp(tx.block_time.unwrap_or_default()),
+        difficulty: event.difficulty.to_string(),
+        base_reward: if event.net_base_reward > 0 {
+            format!("{:#.11}", amount_u64_to_f64(event.net_base_reward))
+        } else {
+            "0".to_string()
+        },
+        boost_reward: if event.net_miner_boost_reward > 0 {
+            format!("{:#.11}", amount_u64_to_f64(event.net_miner_boost_reward))
+        } else {
+            "0".to_string()
+        },
+        total_reward: if event.net_reward > 0 {
+            format!("{:#.11}", amount_u64_to_f64(event.net_reward))
+        } else {
+            "0".to_string()
+        },
+        timing: format!("{}s", event.timing),
+        status: "Confirmed".bold().green().to_string(),
+    })
+}
@@ -0,0 +1,41 @@
+use clap::Command;
+use colored::*;
+
+use crate::{
+    args::{ConfigArgs, ConfigCommand},
+    error::Error,
+    utils::{is_option, BitzConfig},
+    Miner,
+};
+
+impl Miner {
+    /// Reads and writes the bitz config file. Runs before the miner is set up,
+    /// so it works without a keypair or rpc.
+    pub fn config(args: ConfigArgs, profile: Option<&str>, cmd: &Command) -> Result<(), Error> {
+        let mut config = BitzConfig::load()?;
+        match args.command {
+            ConfigCommand::Get(args) => match config.get(&args.key, profile)? {
+                Some(toml::Value::String(value)) => println!("{}", value),
+                Some(value) => println!("{}", value),
+                None => println!("{} is not set", args.key),
+            },
+            ConfigCommand::Set(args) => {
+                if !is_option(cmd, &args.key) {
+                    return Err(Error::Internal(format!("unknown option {}", args.key)));
+                }
+                config.set(&args.key, &args.value, profile)?;
+                println!("Set {} to {}", args.key.bold(), args.value);
+            }
+            ConfigCommand::Show(_) => {
+                let resolved = config.resolve(profile)?;
+                println!("Config file: {}", config.path.display());
+                println!("Profile: {}", profile.unwrap_or("default"));
+                println!();
+                let data = toml::to_string_pretty(&resolved)
+                    .map_err(|err| Error::Internal(err.to_string()))?;
+                println!("{}", data);
+            }
+        }
+        Ok(())
+    }
+}
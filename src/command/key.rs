@@ -0,0 +1,369 @@
+use std::path::Path;
+
+use bip39::Mnemonic;
+use colored::*;
+use eore_api::state::proof_pda;
+use rand::RngCore;
+use serde::Serialize;
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    signature::{Keypair, Signer},
+    signer::keypair::keypair_from_seed_and_derivation_path,
+};
+use tabled::{
+    settings::{object::Rows, Color, Style},
+    Table, Tabled,
+};
+
+use crate::{
+    args::{
+        KeyArgs, KeyCommand, KeyDerivationArgs, KeyDeriveArgs, KeyEncryptArgs, KeyExportArgs,
+        KeyImportArgs, KeyNewArgs,
+    },
+    error::Error,
+    signer::{
+        load_signer, parse_keypair, read_keypair_from_file, read_keypair_from_stdin, serve_signer,
+    },
+    utils::{read_new_passphrase, read_passphrase, write_secret_file, Keystore},
+    Miner,
+};
+
+/// Environment variable holding the mnemonic to derive keypairs from.
+pub const MNEMONIC_ENV: &str = "BITZ_MNEMONIC";
+/// Environment variable holding the BIP39 passphrase of the mnemonic.
+pub const MNEMONIC_PASSPHRASE_ENV: &str = "BITZ_MNEMONIC_PASSPHRASE";
+
+impl Miner {
+    /// Manages keypair files and serves signers. Runs before the miner is set up,
+    /// so keystores are only unlocked when a command needs the secret key.
+    pub async fn key(
+        args: KeyArgs,
+        keypair: Option<String>,
+        proxy: Option<String>,
+    ) -> Result<(), Error> {
+        let default_file = || {
+            keypair
+                .clone()
+                .ok_or_else(|| Error::Internal("no keypair provided".to_string()))
+        };
+        match args.command {
+            KeyCommand::Import(args) => key_import(args),
+            KeyCommand::Export(args) => {
+                let file = args.file.clone().map_or_else(default_file, Ok)?;
+                key_export(args, file)
+            }
+            KeyCommand::Encrypt(args) => {
+                let file = args.file.clone().map_or_else(default_file, Ok)?;
+                key_encrypt(args, file)
+            }
+            KeyCommand::New(args) => key_new(args),
+            KeyCommand::Derive(args) => key_derive(args),
+            KeyCommand::Serve(args) => {
+                let signer = load_signer(&default_file()?, proxy.as_deref())?;
+                serve_signer(signer, &args.listen).await
+            }
+        }
+    }
+}
+
+fn key_import(args: KeyImportArgs) -> Result<(), Error> {
+    let keypair = match args.source {
+        Some(source) if std::path::Path::new(&source).exists() => read_keypair_from_file(&source)?,
+        Some(source) => parse_keypair(&source)?,
+        None => read_keypair_from_stdin()?,
+    };
+    let passphrase = if args.plaintext {
+        None
+    } else {
+        Some(read_new_passphrase()?)
+    };
+    write_keypair(&keypair, &args.output, "json", passphrase.as_deref())?;
+    println!(
+        "Imported {} into {}",
+        keypair.pubkey().to_string().bold(),
+        args.output
+    );
+    Ok(())
+}
+
+fn key_export(args: KeyExportArgs, file: String) -> Result<(), Error> {
+    let keypair = read_keypair_from_file(&file)?;
+    let secret = format_secret(&keypair, &args.format);
+    match args.output {
+        Some(output) => {
+            write_secret_file(&output, secret.as_bytes())?;
+            println!(
+                "Exported {} to {}",
+                keypair.pubkey().to_string().bold(),
+                output
+            );
+        }
+        None => {
+            eprintln!(
+                "{} The secret key of {} is printed in plaintext.",
+                "WARNING".bold().yellow(),
+                keypair.pubkey()
+            );
+            println!("{}", secret);
+        }
+    }
+    Ok(())
+}
+
+fn key_encrypt(args: KeyEncryptArgs, file: String) -> Result<(), Error> {
+    let is_keystore = std::fs::read_to_string(&file)
+        .map(|content| Keystore::parse(&content).is_some())
+        .unwrap_or(false);
+    if is_keystore {
+        println!("{} is already encrypted", file);
+        return Ok(());
+    }
+    let keypair = read_keypair_from_file(&file)?;
+    let output = args.output.unwrap_or(file);
+    let passphrase = read_new_passphrase()?;
+    write_keypair(&keypair, &output, "json", Some(&passphrase))?;
+    println!(
+        "Encrypted {} into {}",
+        keypair.pubkey().to_string().bold(),
+        output
+    );
+    Ok(())
+}
+
+fn key_new(args: KeyNewArgs) -> Result<(), Error> {
+    let entropy_len = match args.word_count {
+        12 => 16,
+        24 => 32,
+        count => {
+            return Err(Error::Internal(format!(
+                "unsupported word count {}, use 12 or 24",
+                count
+            )))
+        }
+    };
+    check_overwrite(&args.output, args.derivation.force)?;
+    let mut entropy = vec![0u8; entropy_len];
+    rand::thread_rng().fill_bytes(&mut entropy);
+    let mnemonic = Mnemonic::from_entropy(&entropy)
+        .map_err(|err| Error::Internal(format!("failed to generate mnemonic: {}", err)))?;
+    let seed = mnemonic.to_seed(read_bip39_passphrase(&args.derivation, true)?);
+
+    // Write the first account of the mnemonic
+    let passphrase = if args.derivation.encrypt {
+        Some(read_new_passphrase()?)
+    } else {
+        None
+    };
+    let entry = derive_and_write(
+        &seed,
+        0,
+        &args.output,
+        &args.derivation.format,
+        passphrase.as_deref(),
+    )?;
+
+    // Show the mnemonic once
+    println!(
+        "{} Write down the mnemonic and keep it offline. It recovers this keypair, and derives more with `bitz key derive`.",
+        "IMPORTANT".bold().yellow()
+    );
+    println!("\n{}\n", mnemonic.to_string().bold());
+    print_manifest(vec![entry]);
+    Ok(())
+}
+
+fn key_derive(args: KeyDeriveArgs) -> Result<(), Error> {
+    let end = args
+        .start
+        .checked_add(args.count)
+        .ok_or_else(|| Error::Internal("account index out of range".to_string()))?;
+    let extension = match args.derivation.format.as_str() {
+        "base58" if !args.derivation.encrypt => "txt",
+        _ => "json",
+    };
+    let files: Vec<(u32, String)> = (args.start..end)
+        .map(|index| {
+            let file = Path::new(&args.output_dir).join(format!("miner-{}.{}", index, extension));
+            (index, file.to_string_lossy().to_string())
+        })
+        .collect();
+    for (_, file) in files.iter() {
+        check_overwrite(file, args.derivation.force)?;
+    }
+
+    // Read the mnemonic
+    let phrase = match std::env::var(MNEMONIC_ENV) {
+        Ok(phrase) => phrase,
+        Err(_) => read_passphrase("Mnemonic: ")?,
+    };
+    let mnemonic = Mnemonic::parse_normalized(phrase.trim())
+        .map_err(|err| Error::Internal(format!("invalid mnemonic: {}", err)))?;
+    let seed = mnemonic.to_seed(read_bip39_passphrase(&args.derivation, false)?);
+
+    // Derive and write the keypairs
+    let passphrase = if args.derivation.encrypt {
+        Some(read_new_passphrase()?)
+    } else {
+        None
+    };
+    std::fs::create_dir_all(&args.output_dir)
+        .map_err(|err| Error::Internal(format!("failed to create {}: {}", args.output_dir, err)))?;
+    let mut manifest = vec![];
+    for (index, file) in files {
+        manifest.push(derive_and_write(
+            &seed,
+            index,
+            &file,
+            &args.derivation.format,
+            passphrase.as_deref(),
+        )?);
+    }
+
+    // Write the manifest next to the keypairs
+    let manifest_file = Path::new(&args.output_dir).join("manifest.json");
+    let data =
+        serde_json::to_string_pretty(&manifest).map_err(|err| Error::Internal(err.to_string()))?;
+    std::fs::write(&manifest_file, data).map_err(|err| {
+        Error::Internal(format!(
+            "failed to write {}: {}",
+            manifest_file.display(),
+            err
+        ))
+    })?;
+    print_manifest(manifest);
+    println!("Wrote manifest to {}", manifest_file.display());
+    Ok(())
+}
+
+/// A derived keypair, as listed in the manifest.
+#[derive(Serialize, Tabled)]
+struct KeyManifestEntry {
+    #[tabled(rename = "Index")]
+    index: u32,
+    #[tabled(rename = "Derivation path")]
+    derivation_path: String,
+    #[tabled(rename = "Pubkey")]
+    pubkey: String,
+    #[tabled(rename = "Proof")]
+    proof: String,
+    #[tabled(rename = "File")]
+    file: String,
+}
+
+/// Derives the keypair of an account index with the standard Solana path,
+/// m/44'/501'/index'/0', and writes it to the file.
+fn derive_and_write(
+    seed: &[u8],
+    index: u32,
+    file: &str,
+    format: &str,
+    passphrase: Option<&str>,
+) -> Result<KeyManifestEntry, Error> {
+    let keypair = derive_keypair(seed, index)?;
+    write_keypair(&keypair, file, format, passphrase)?;
+    Ok(KeyManifestEntry {
+        index,
+        derivation_path: derivation_path_string(index),
+        pubkey: keypair.pubkey().to_string(),
+        proof: proof_pda(keypair.pubkey()).0.to_string(),
+        file: file.to_string(),
+    })
+}
+
+/// Derives the keypair of an account index, like `solana-keygen` does for
+/// `prompt://?key=index/0`.
+fn derive_keypair(seed: &[u8], index: u32) -> Result<Keypair, Error> {
+    let derivation_path = DerivationPath::new_bip44(Some(index), Some(0));
+    keypair_from_seed_and_derivation_path(seed, Some(derivation_path)).map_err(|err| {
+        Error::Internal(format!(
+            "failed to derive keypair {}: {}",
+            derivation_path_string(index),
+            err
+        ))
+    })
+}
+
+fn derivation_path_string(index: u32) -> String {
+    format!("m/44'/501'/{}'/0'", index)
+}
+
+fn print_manifest(manifest: Vec<KeyManifestEntry>) {
+    let mut table = Table::new(manifest);
+    table.with(Style::blank());
+    table.modify(Rows::first(), Color::BOLD);
+    println!("{table}\n");
+}
+
+fn read_bip39_passphrase(args: &KeyDerivationArgs, confirm: bool) -> Result<String, Error> {
+    if !args.bip39_passphrase {
+        return Ok(String::new());
+    }
+    if let Ok(passphrase) = std::env::var(MNEMONIC_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase("BIP39 passphrase: ")?;
+    if confirm && read_passphrase("Repeat BIP39 passphrase: ")? != passphrase {
+        return Err(Error::Internal("passphrases do not match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+fn check_overwrite(file: &str, force: bool) -> Result<(), Error> {
+    if !force && Path::new(file).exists() {
+        return Err(Error::Internal(format!(
+            "{} already exists, pass --force to overwrite it",
+            file
+        )));
+    }
+    Ok(())
+}
+
+/// Writes a keypair as an encrypted keystore if given a passphrase, otherwise
+/// as a plaintext secret key in the format.
+fn write_keypair(
+    keypair: &Keypair,
+    path: &str,
+    format: &str,
+    passphrase: Option<&str>,
+) -> Result<(), Error> {
+    match passphrase {
+        Some(passphrase) => Keystore::encrypt(keypair, passphrase)?.write(path),
+        None => write_secret_file(path, format_secret(keypair, format).as_bytes()),
+    }
+}
+
+/// Formats a secret key as a JSON byte array, like solana keypair files, or
+/// as base58.
+fn format_secret(keypair: &Keypair, format: &str) -> String {
+    match format {
+        "json" => format!("{:?}", keypair.to_bytes().to_vec()).replace(' ', ""),
+        _ => keypair.to_base58_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn derive_keypair_matches_solana_keygen() {
+        // solana-keygen pubkey "prompt://?key=0/0" with the mnemonic above
+        let seed = Mnemonic::parse_normalized(MNEMONIC).unwrap().to_seed("");
+        let keypair = derive_keypair(&seed, 0).unwrap();
+        assert_eq!(
+            keypair.pubkey().to_string(),
+            "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk"
+        );
+        assert_eq!(derivation_path_string(0), "m/44'/501'/0'/0'");
+    }
+
+    #[test]
+    fn derive_keypair_differs_per_index() {
+        let seed = Mnemonic::parse_normalized(MNEMONIC).unwrap().to_seed("");
+        let first = derive_keypair(&seed, 0).unwrap();
+        let second = derive_keypair(&seed, 1).unwrap();
+        assert_ne!(first.pubkey(), second.pubkey());
+    }
+}
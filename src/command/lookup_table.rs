@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use colored::*;
+use eore_api::{
+    consts::{
+        BUS_ADDRESSES, CONFIG_ADDRESS, MINT_ADDRESS, TREASURY_ADDRESS, TREASURY_TOKENS_ADDRESS,
+    },
+    state::proof_pda,
+};
+use eore_boost_api::state::{boost_pda, config_pda, stake_pda};
+use solana_program::{
+    address_lookup_table::instruction::{create_lookup_table, extend_lookup_table},
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signer};
+use tabled::{
+    settings::{
+        object::{Columns, Rows},
+        Alignment, Remove, Style,
+    },
+    Table,
+};
+
+use crate::{
+    args::{LookupTableArgs, LookupTableCommand, LookupTableCreateArgs, LookupTableExtendArgs},
+    error::Error,
+    utils::{get_lookup_table, ComputeBudget, TableData, TableSectionTitle},
+    Miner,
+};
+
+/// Addresses added per extend instruction, to stay within the transaction size limit.
+const EXTEND_CHUNK_SIZE: usize = 20;
+
+impl Miner {
+    pub async fn lookup_table(&self, args: LookupTableArgs) -> Result<(), Error> {
+        match args.command {
+            Some(LookupTableCommand::Create(args)) => self.lookup_table_create(args).await,
+            Some(LookupTableCommand::Extend(args)) => self.lookup_table_extend(args).await,
+            None => match args.address {
+                Some(address) => self.lookup_table_get(Pubkey::from_str(&address)?).await,
+                None => Err(Error::Internal(
+                    "no lookup table address provided".to_string(),
+                )),
+            },
+        }
+    }
+
+    async fn lookup_table_create(&self, args: LookupTableCreateArgs) -> Result<(), Error> {
+        let authority = self.signer().pubkey();
+        let slot = self
+            .rpc_client
+            .get_slot_with_commitment(CommitmentConfig::finalized())
+            .await?;
+        let (ix, address) = create_lookup_table(authority, authority, slot);
+        self.send_and_confirm(&[ix], ComputeBudget::Fixed(50_000), false)
+            .await?;
+        println!("Created lookup table {}", address.to_string().bold());
+
+        // Add the default accounts
+        if !args.empty {
+            self.extend_lookup_table(address, default_accounts(authority))
+                .await?;
+        }
+        println!(
+            "Send versioned transactions with `--lookup-table {}`",
+            address
+        );
+        Ok(())
+    }
+
+    async fn lookup_table_extend(&self, args: LookupTableExtendArgs) -> Result<(), Error> {
+        let address = Pubkey::from_str(&args.address)?;
+        let accounts = if args.accounts.is_empty() {
+            default_accounts(self.signer().pubkey())
+        } else {
+            args.accounts
+                .iter()
+                .map(|account| Pubkey::from_str(account))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        self.extend_lookup_table(address, accounts).await
+    }
+
+    /// Adds the accounts the lookup table doesn't hold yet.
+    async fn extend_lookup_table(
+        &self,
+        address: Pubkey,
+        accounts: Vec<Pubkey>,
+    ) -> Result<(), Error> {
+        let authority = self.signer().pubkey();
+        let existing = match get_lookup_table(&self.rpc_client, address).await {
+            Ok(lookup_table) => lookup_table.addresses,
+            Err(_) => vec![],
+        };
+        let mut accounts: Vec<Pubkey> = accounts
+            .into_iter()
+            .filter(|account| !existing.contains(account))
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+        if accounts.is_empty() {
+            println!("Lookup table already holds all accounts");
+            return Ok(());
+        }
+        for chunk in accounts.chunks(EXTEND_CHUNK_SIZE) {
+            let ix = extend_lookup_table(address, authority, Some(authority), chunk.to_vec());
+            self.send_and_confirm(&[ix], ComputeBudget::Fixed(50_000), false)
+                .await?;
+        }
+        println!(
+            "Added {} accounts to lookup table {}",
+            accounts.len(),
+            address
+        );
+        Ok(())
+    }
+
+    async fn lookup_table_get(&self, address: Pubkey) -> Result<(), Error> {
+        let lookup_table = get_lookup_table(&self.rpc_client, address)
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        // Aggregate data
+        let mut data = vec![TableData {
+            key: "Address".to_string(),
+            value: address.to_string(),
+        }];
+        for (i, account) in lookup_table.addresses.iter().enumerate() {
+            data.push(TableData {
+                key: format!("#{}", i),
+                value: account.to_string(),
+            });
+        }
+
+        // Build table
+        let mut table = Table::new(data);
+        table.with(Remove::row(Rows::first()));
+        table.modify(Columns::single(1), Alignment::right());
+        table.with(Style::blank());
+        table.section_title(0, "Lookup table");
+        println!("{table}\n");
+        Ok(())
+    }
+}
+
+/// Accounts that mining, claiming and staking transactions of the authority
+/// touch: the ORE program state, its proof and token account, and its BITZ
+/// boost stake.
+fn default_accounts(authority: Pubkey) -> Vec<Pubkey> {
+    let boost_address = boost_pda(MINT_ADDRESS).0;
+    let boost_config_address = config_pda().0;
+    let mut accounts = vec![
+        CONFIG_ADDRESS,
+        TREASURY_ADDRESS,
+        TREASURY_TOKENS_ADDRESS,
+        MINT_ADDRESS,
+        proof_pda(authority).0,
+        spl_associated_token_account::get_associated_token_address(&authority, &MINT_ADDRESS),
+        boost_config_address,
+        proof_pda(boost_config_address).0,
+        boost_address,
+        spl_associated_token_account::get_associated_token_address(&boost_address, &MINT_ADDRESS),
+        stake_pda(authority, boost_address).0,
+        sysvar::slot_hashes::ID,
+        sysvar::instructions::ID,
+    ];
+    accounts.extend_from_slice(&BUS_ADDRESSES);
+    accounts
+}
@@ -1,11 +1,19 @@
 mod account;
 mod benchmark;
 mod claim;
+mod config;
 #[cfg(feature = "admin")]
 mod initialize;
 mod collect;
+mod key;
+mod lookup_table;
+mod nonce;
 mod pool;
+mod pool_mock;
+mod pool_operator;
+mod pool_server;
 mod program;
 mod stake;
 mod transaction;
 mod transfer;
+mod tx;
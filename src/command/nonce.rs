@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use colored::*;
+use solana_program::{
+    native_token::lamports_to_sol, pubkey::Pubkey, system_instruction, system_program,
+};
+use solana_sdk::{nonce, signature::Signer};
+use tabled::{
+    settings::{
+        object::{Columns, Rows},
+        Alignment, Remove, Style,
+    },
+    Table,
+};
+
+use crate::{
+    args::{NonceArgs, NonceCommand, NonceCreateArgs},
+    error::Error,
+    utils::{get_nonce, ComputeBudget, TableData, TableSectionTitle},
+    Miner,
+};
+
+/// Seed of the nonce account used when no address is given.
+const DEFAULT_NONCE_SEED: &str = "nonce";
+
+impl Miner {
+    pub async fn nonce(&self, args: NonceArgs) -> Result<(), Error> {
+        match args.command {
+            Some(NonceCommand::Create(args)) => self.nonce_create(args).await,
+            None => self.nonce_get(args).await,
+        }
+    }
+
+    async fn nonce_create(&self, args: NonceCreateArgs) -> Result<(), Error> {
+        let signer = self.signer();
+        let address = nonce_address(signer.pubkey(), &args.seed)?;
+        let authority = match args.authority {
+            Some(authority) => Pubkey::from_str(&authority)?,
+            None => signer.pubkey(),
+        };
+
+        // Check if the account already exists
+        if self.rpc_client.get_account_data(&address).await.is_ok() {
+            println!("Nonce account {} already exists", address);
+            return Ok(());
+        }
+
+        // Create and initialize the account
+        let lamports = self
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(nonce::State::size())
+            .await?;
+        let ixs = system_instruction::create_nonce_account_with_seed(
+            &signer.pubkey(),
+            &address,
+            &signer.pubkey(),
+            &args.seed,
+            &authority,
+            lamports,
+        );
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
+            .await?;
+        println!("Created nonce account {}", address.to_string().bold());
+        println!("Sign against it with `--nonce {}`", address);
+        Ok(())
+    }
+
+    async fn nonce_get(&self, args: NonceArgs) -> Result<(), Error> {
+        let address = match args.address {
+            Some(address) => Pubkey::from_str(&address)?,
+            None => nonce_address(self.signer().pubkey(), DEFAULT_NONCE_SEED)?,
+        };
+        let nonce = get_nonce(&self.rpc_client, address)
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+        let balance = self.rpc_client.get_balance(&address).await?;
+
+        // Aggregate data
+        let data = vec![
+            TableData {
+                key: "Address".to_string(),
+                value: address.to_string(),
+            },
+            TableData {
+                key: "Authority".to_string(),
+                value: nonce.authority.to_string(),
+            },
+            TableData {
+                key: "Nonce".to_string(),
+                value: nonce.blockhash().to_string(),
+            },
+            TableData {
+                key: "Fee".to_string(),
+                value: format!(
+                    "{} lamports per signature",
+                    nonce.fee_calculator.lamports_per_signature
+                ),
+            },
+            TableData {
+                key: "Balance".to_string(),
+                value: format!("{} ETH", lamports_to_sol(balance)),
+            },
+        ];
+
+        // Build table
+        let mut table = Table::new(data);
+        table.with(Remove::row(Rows::first()));
+        table.modify(Columns::single(1), Alignment::right());
+        table.with(Style::blank());
+        table.section_title(0, "Nonce");
+        println!("{table}\n");
+        Ok(())
+    }
+}
+
+/// Address of the nonce account derived from the authority keypair and a seed.
+fn nonce_address(base: Pubkey, seed: &str) -> Result<Pubkey, Error> {
+    Pubkey::create_with_seed(&base, seed, &system_program::ID)
+        .map_err(|err| Error::Internal(format!("invalid nonce seed {}: {}", seed, err)))
+}
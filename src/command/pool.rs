@@ -1,18 +1,23 @@
-use std::sync::Arc;
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 
 use crossterm::style::Stylize;
 use drillx::Solution;
 use eore_api::state::proof_pda;
-use ore_pool_api::state::member_pda;
+use eore_boost_api::state::{boost_pda, stake_pda};
+use ore_pool_api::state::{member_pda, share_pda};
 use ore_pool_types::{
     BalanceUpdate, ContributePayload, Member, MemberChallenge, PoolAddress, RegisterPayload,
     UpdateBalancePayload,
 };
-use reqwest::StatusCode;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Certificate, Identity, Response,
+};
 use solana_rpc_client::spinner;
 use solana_sdk::{
     compute_budget, pubkey::Pubkey, signature::Signature, signer::Signer, transaction::Transaction,
 };
+use spl_token::amount_to_ui_amount;
 use steel::AccountDeserialize;
 use tabled::{
     settings::{
@@ -24,15 +29,21 @@ use tabled::{
 };
 
 use crate::{
-    args::{PoolArgs, PoolCommand, PoolCommitArgs},
-    error::Error,
+    args::{
+        PoolArgs, PoolCommand, PoolCommitArgs, PoolLeaveArgs, PoolSharesArgs, PoolStakeArgs,
+        PoolUnstakeArgs, PoolWatchArgs,
+    },
+    error::{Error, PoolError},
     utils::{
-        self, amount_u64_to_f64, format_timestamp, get_member, get_pool, get_pools, get_proof,
-        ComputeBudget, TableData, TableSectionTitle,
+        self, amount_f64_to_u64, amount_u64_to_f64, ask_confirm, format_timestamp, get_boost,
+        get_boosts, get_member, get_mint, get_pool, get_pools, get_proof, get_share, ComputeBudget,
+        TableData, TableSectionTitle,
     },
     Miner,
 };
 
+use super::pool_mock::mock_pool_address;
+
 impl Miner {
     // TODO
     pub async fn pool(&self, args: PoolArgs) {
@@ -41,6 +52,19 @@ impl Miner {
                 PoolCommand::Commit(commit_args) => {
                     self.pool_commit(args, commit_args).await.unwrap()
                 }
+                PoolCommand::Watch(watch_args) => self.pool_watch(args, watch_args).await.unwrap(),
+                PoolCommand::Leave(leave_args) => self.pool_leave(args, leave_args).await.unwrap(),
+                PoolCommand::Stake(stake_args) => self.pool_stake(args, stake_args).await.unwrap(),
+                PoolCommand::Unstake(unstake_args) => {
+                    self.pool_unstake(args, unstake_args).await.unwrap()
+                }
+                PoolCommand::Shares(shares_args) => {
+                    self.pool_shares(args, shares_args).await.unwrap()
+                }
+                PoolCommand::ServeMock(mock_args) => self.pool_serve_mock(mock_args).await.unwrap(),
+                PoolCommand::Operate(operate_args) => {
+                    self.pool_operate(operate_args).await.unwrap()
+                }
             }
         } else {
             if let Some(pool_url) = args.pool_url {
@@ -57,8 +81,7 @@ impl Miner {
             .expect("Failed to fetch pool accounts");
         let mut data = vec![];
         for (pool_address, pool) in pools {
-            let url = String::from_utf8(pool.url.to_vec()).unwrap_or_default();
-            let url = url.trim_end_matches('\0');
+            let url = pool_url_from_account(&pool);
             let mut point = PoolTableData {
                 address: pool_address.to_string(),
                 url: url.to_string(),
@@ -98,10 +121,7 @@ impl Miner {
 
     async fn get_pool(&self, pool_url: String) -> Result<(), Error> {
         // build pool client
-        let pool = Pool {
-            http_client: reqwest::Client::new(),
-            pool_url: pool_url.clone(),
-        };
+        let pool = Pool::connect(self, &pool_url).await?;
 
         // Fetch pool account
         let pool_address = pool.get_pool_address().await?.address;
@@ -121,7 +141,7 @@ impl Miner {
         });
         data.push(TableData {
             key: "Url".to_string(),
-            value: pool_url.clone(),
+            value: pool.pool_url.clone(),
         });
 
         // Get proof account
@@ -174,8 +194,7 @@ impl Miner {
                     .to_string(),
             });
             // Get offchain data from pool server
-            if let Ok(member_offchain) = pool.get_pool_member(&self).await {
-                let pending_rewards = (member_offchain.total_balance as u64) - member.total_balance;
+            if let Ok(pending_rewards) = pool.get_pending_rewards(self, pool_address).await {
                 data.push(TableData {
                     key: "Pending rewards".to_string(),
                     value: format!("{} BITZ", utils::amount_u64_to_string(pending_rewards)),
@@ -205,30 +224,622 @@ impl Miner {
     }
 
     async fn pool_commit(&self, args: PoolArgs, _commit_args: PoolCommitArgs) -> Result<(), Error> {
-        let pool = Pool {
-            http_client: reqwest::Client::new(),
-            pool_url: args.pool_url.expect("Pool URL is required"),
-        };
+        let pool = Pool::connect(self, &args.pool_url.expect("Pool URL is required")).await?;
         if let Err(err) = pool.post_update_balance(self).await {
             println!("{:?}", err);
         }
         Ok(())
     }
+
+    async fn pool_watch(&self, args: PoolArgs, watch_args: PoolWatchArgs) -> Result<(), Error> {
+        let pool = Pool::connect(self, &args.pool_url.expect("Pool URL is required")).await?;
+        let threshold = amount_f64_to_u64(watch_args.threshold);
+        let claim_to = watch_args
+            .claim_to
+            .map(|to| Pubkey::from_str(&to).expect("Failed to parse wallet address"));
+        loop {
+            if let Err(err) = self
+                .commit_pending_rewards(&pool, threshold, claim_to)
+                .await
+            {
+                println!("{} {:?}", "ERROR".bold().red(), err);
+            }
+            tokio::time::sleep(Duration::from_secs(watch_args.interval)).await;
+        }
+    }
+
+    /// Commits and claims the member's whole pool balance. The pool program has
+    /// no instruction to close member accounts, so the member account and the
+    /// rent it holds are left as they are.
+    async fn pool_leave(&self, args: PoolArgs, leave_args: PoolLeaveArgs) -> Result<(), Error> {
+        let pool = Pool::connect(self, &args.pool_url.expect("Pool URL is required")).await?;
+        let pool_address = pool.get_pool_address().await?.address;
+        let member_address = member_pda(self.signer().pubkey(), pool_address).0;
+        let wallet = match leave_args.to {
+            Some(to) => Pubkey::from_str(&to).expect("Failed to parse wallet address"),
+            None => self.signer().pubkey(),
+        };
+
+        // Confirm user wants to leave
+        let member = pool.get_pool_member_onchain(self, pool_address).await?;
+        let pending_rewards = pool.get_pending_rewards(self, pool_address).await?;
+        if !ask_confirm(
+            format!(
+                "\nYou are about to claim your whole balance of {} BITZ ({} BITZ pending) from pool {} to {}.\nYour member account stays open and its rent is not refunded.\n\nAre you sure you want to continue? [Y/n]",
+                utils::amount_u64_to_string(member.balance.saturating_add(pending_rewards)),
+                utils::amount_u64_to_string(pending_rewards),
+                pool_address,
+                wallet,
+            )
+            .as_str(),
+        ) {
+            return Ok(());
+        }
+
+        // Commit pending rewards and claim them, or claim what is already committed
+        if !self.commit_pending_rewards(&pool, 1, Some(wallet)).await? {
+            let member = pool.get_pool_member_onchain(self, pool_address).await?;
+            if member.balance > 0 {
+                self.claim_from_pool_to(pool_address, wallet, member.balance)
+                    .await?;
+            }
+        }
+
+        // The pool program has no instruction to close member accounts
+        println!(
+            "{} The pool program does not support closing member accounts. The rent held by {} cannot be reclaimed.",
+            "NOTE".bold().yellow(),
+            member_address
+        );
+        println!("Stop collecting with this pool before joining another one.");
+        Ok(())
+    }
+
+    /// Staking has moved from pools to the global boost program, so this
+    /// deposits into the global boost for the mint.
+    async fn pool_stake(&self, _args: PoolArgs, stake_args: PoolStakeArgs) -> Result<(), Error> {
+        let signer = self.signer();
+        let mint_address = match stake_args.mint {
+            Some(mint_str) => Pubkey::from_str(&mint_str).expect("Failed to parse mint address"),
+            None => eore_api::consts::MINT_ADDRESS,
+        };
+
+        // Get sender token account
+        let sender = match &stake_args.token_account {
+            Some(address) => {
+                Pubkey::from_str(address).expect("Failed to parse token account address")
+            }
+            None => spl_associated_token_account::get_associated_token_address(
+                &signer.pubkey(),
+                &mint_address,
+            ),
+        };
+        let mint = get_mint(&self.rpc_client, mint_address)
+            .await
+            .map_err(|err| Error::Internal(format!("Failed to fetch mint account: {}", err)))?;
+        let token_account = match self.rpc_client.get_token_account(&sender).await {
+            Ok(Some(account)) => account,
+            _ => {
+                println!("{}: Token account not found", "ERROR".bold().red());
+                return Err(Error::Internal("Token account not found".to_string()));
+            }
+        };
+
+        // Check the mint has a boost
+        let boost_address = boost_pda(mint_address).0;
+        if get_boost(&self.rpc_client, boost_address).await.is_err() {
+            println!(
+                "{}: No boost found for mint {}",
+                "ERROR".bold().red(),
+                mint_address
+            );
+            return Err(Error::Internal("Boost account not found".to_string()));
+        }
+
+        // Parse amount
+        let amount: u64 = if let Some(amount) = stake_args.amount {
+            (amount * 10f64.powf(mint.decimals as f64)) as u64
+        } else {
+            u64::from_str(token_account.token_amount.amount.as_str())?
+        };
+
+        // Confirm user wants to stake
+        if !ask_confirm(
+            format!(
+                "\nPool staking has moved to the global boost program. You are about to stake {} tokens of mint {} in its global boost.\n\nAre you sure you want to continue? [Y/n]",
+                amount_to_ui_amount(amount, mint.decimals),
+                mint_address,
+            )
+            .as_str(),
+        ) {
+            return Ok(());
+        }
+
+        // Open stake account, if needed
+        let mut ixs = vec![];
+        let stake_address = stake_pda(signer.pubkey(), boost_address).0;
+        if self
+            .rpc_client
+            .get_account_data(&stake_address)
+            .await
+            .is_err()
+        {
+            ixs.push(eore_boost_api::sdk::open(
+                signer.pubkey(),
+                signer.pubkey(),
+                mint_address,
+            ));
+        }
+
+        // Send and confirm
+        ixs.push(eore_boost_api::sdk::deposit(
+            signer.pubkey(),
+            mint_address,
+            amount,
+        ));
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
+            .await?;
+        Ok(())
+    }
+
+    async fn pool_unstake(
+        &self,
+        args: PoolArgs,
+        unstake_args: PoolUnstakeArgs,
+    ) -> Result<(), Error> {
+        let pool = Pool::connect(self, &args.pool_url.expect("Pool URL is required")).await?;
+        let pool_address = pool.get_pool_address().await?.address;
+        let signer = self.signer();
+        let mint_address = match unstake_args.mint {
+            Some(mint_str) => Pubkey::from_str(&mint_str).expect("Failed to parse mint address"),
+            None => eore_api::consts::MINT_ADDRESS,
+        };
+
+        // Get recipient token account, creating it if necessary
+        let mut ixs = vec![];
+        let recipient = match &unstake_args.token_account {
+            Some(address) => {
+                Pubkey::from_str(address).expect("Failed to parse token account address")
+            }
+            None => spl_associated_token_account::get_associated_token_address(
+                &signer.pubkey(),
+                &mint_address,
+            ),
+        };
+        if self.rpc_client.get_token_account(&recipient).await.is_err() {
+            ixs.push(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &signer.pubkey(),
+                    &signer.pubkey(),
+                    &mint_address,
+                    &spl_token::id(),
+                ),
+            );
+        }
+
+        // Check share account exists
+        let mint = get_mint(&self.rpc_client, mint_address)
+            .await
+            .map_err(|err| Error::Internal(format!("Failed to fetch mint account: {}", err)))?;
+        let share = match pool
+            .get_staker_onchain(self, pool_address, mint_address)
+            .await
+        {
+            Ok(share) => share,
+            Err(err) => {
+                println!(
+                    "{}: No share account found for this mint.",
+                    "ERROR".bold().red()
+                );
+                println!("Error details: {:?}", err);
+                return Err(Error::Internal("Share account not found".to_string()));
+            }
+        };
+
+        // Parse amount
+        let amount: u64 = if let Some(amount) = unstake_args.amount {
+            (amount * 10f64.powf(mint.decimals as f64)) as u64
+        } else {
+            share.balance
+        };
+
+        // Confirm user wants to unstake
+        if !ask_confirm(
+            format!(
+                "\nYou are about to unstake {} tokens of mint {} from pool {}.\n\nAre you sure you want to continue? [Y/n]",
+                amount_to_ui_amount(amount, mint.decimals),
+                mint_address,
+                pool_address,
+            )
+            .as_str(),
+        ) {
+            return Ok(());
+        }
+
+        // Send and confirm
+        ixs.push(ore_pool_api::sdk::unstake(
+            signer.pubkey(),
+            mint_address,
+            pool_address,
+            recipient,
+            amount,
+        ));
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
+            .await?;
+        Ok(())
+    }
+
+    async fn pool_shares(&self, args: PoolArgs, shares_args: PoolSharesArgs) -> Result<(), Error> {
+        let pool = Pool::connect(self, &args.pool_url.expect("Pool URL is required")).await?;
+        let pool_address = pool.get_pool_address().await?.address;
+        let authority = match &shares_args.authority {
+            Some(authority) => {
+                Pubkey::from_str(authority).expect("Failed to parse account address")
+            }
+            None => self.signer().pubkey(),
+        };
+
+        // Look up a share account for every boost mint
+        let mut data = vec![];
+        let boosts = get_boosts(&self.rpc_client)
+            .await
+            .expect("Failed to fetch boosts");
+        for (_address, boost) in boosts {
+            let share_address = share_pda(authority, pool_address, boost.mint).0;
+            let Ok(share) = get_share(&self.rpc_client, share_address).await else {
+                continue;
+            };
+            let mint = get_mint(&self.rpc_client, boost.mint)
+                .await
+                .expect("Failed to fetch mint account");
+            data.push(ShareTableData {
+                mint: boost.mint.to_string(),
+                address: share_address.to_string(),
+                balance: format!("{}", amount_to_ui_amount(share.balance, mint.decimals)),
+            });
+        }
+        if data.is_empty() {
+            println!("No share accounts found in pool {}", pool_address);
+            return Ok(());
+        }
+
+        // Build table
+        let mut table = Table::new(data);
+        table.with(Style::blank());
+        table.modify(Rows::first(), Color::BOLD);
+        table.modify(Columns::new(1..), Alignment::right());
+        table.with(
+            Highlight::new(Rows::single(1)).color(BorderColor::default().top(Color::FG_WHITE)),
+        );
+        table.with(Highlight::new(Rows::single(1)).border(Border::new().top('━')));
+        println!("\n{table}\n");
+        Ok(())
+    }
+
+    /// Commits pending pool rewards once they exceed the threshold, and optionally
+    /// claims the committed balance to the given wallet. Returns whether a commit was made.
+    pub async fn commit_pending_rewards(
+        &self,
+        pool: &Pool,
+        threshold: u64,
+        claim_to: Option<Pubkey>,
+    ) -> Result<bool, Error> {
+        // Check pending rewards against threshold
+        let pool_address = pool.get_pool_address().await?.address;
+        let pending_rewards = pool.get_pending_rewards(self, pool_address).await?;
+        if pending_rewards < threshold {
+            return Ok(false);
+        }
+        println!(
+            "Committing {} BITZ of pending pool rewards...",
+            utils::amount_u64_to_string(pending_rewards)
+        );
+
+        // Sign and post the attribute transaction
+        let member = pool.get_pool_member_onchain(self, pool_address).await?;
+        let expected_total_balance = member.total_balance.saturating_add(pending_rewards);
+        pool.post_update_balance(self).await?;
+
+        // Claim to cold wallet, if requested
+        let Some(wallet) = claim_to else {
+            return Ok(true);
+        };
+        for _ in 0..COMMIT_CONFIRM_RETRIES {
+            let member = pool.get_pool_member_onchain(self, pool_address).await?;
+            if member.total_balance >= expected_total_balance {
+                if member.balance > 0 {
+                    self.claim_from_pool_to(pool_address, wallet, member.balance)
+                        .await?;
+                }
+                return Ok(true);
+            }
+            tokio::time::sleep(Duration::from_secs(COMMIT_CONFIRM_DELAY)).await;
+        }
+        Err(Error::Internal(
+            "Pool operator has not landed the commit yet, skipping claim".to_string(),
+        ))
+    }
 }
 
+const COMMIT_CONFIRM_RETRIES: usize = 30;
+const COMMIT_CONFIRM_DELAY: u64 = 2;
+
+/// Timeout for establishing a connection to the pool server.
+const POOL_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Timeout for a single request to the pool server, including the response body.
+const POOL_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
 #[derive(Clone)]
 pub struct Pool {
     pub http_client: reqwest::Client,
     pub pool_url: String,
+    pub endpoints: PoolEndpoints,
+    /// Whether the server is a `pool serve-mock` server, which has no on-chain
+    /// accounts and hands out challenges on its own clock. Only set through
+    /// `--mock-pool`, see [`Pool::into_mock`].
+    pub mock: bool,
+}
+
+/// Endpoint paths that differ between pool server versions.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolEndpoints {
+    pub address: &'static str,
+    pub commit: &'static str,
+}
+
+impl PoolEndpoints {
+    pub const CURRENT: PoolEndpoints = PoolEndpoints {
+        address: "address",
+        commit: "commit",
+    };
+    pub const LEGACY: PoolEndpoints = PoolEndpoints {
+        address: "pool-address",
+        commit: "update-balance",
+    };
+}
+
+/// Per-pool credentials, read from the file passed with `--pool-auth`. The file
+/// maps pool urls (or on-chain pool addresses) to their settings:
+///
+/// ```json
+/// {
+///   "https://pool.example.com": {
+///     "bearer": "secret-token",
+///     "headers": { "x-api-key": "key" },
+///     "cert": "client.pem",
+///     "key": "client.key"
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct PoolAuth {
+    /// Sent as `Authorization: Bearer <token>`.
+    pub bearer: Option<String>,
+    /// Extra headers sent with every request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// PEM client certificate for mutual TLS. Requires `key`.
+    pub cert: Option<String>,
+    /// PEM (PKCS#8) private key for `cert`.
+    pub key: Option<String>,
+    /// PEM root certificate to trust in addition to the system roots.
+    pub ca_cert: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(transparent)]
+pub struct PoolAuthConfig(HashMap<String, PoolAuth>);
+
+impl PoolAuthConfig {
+    pub fn load(filepath: Option<&str>) -> Result<Self, Error> {
+        let Some(filepath) = filepath else {
+            return Ok(Self::default());
+        };
+        let data = std::fs::read_to_string(filepath)
+            .map_err(|err| Error::Internal(format!("Failed to read {}: {}", filepath, err)))?;
+        serde_json::from_str(&data)
+            .map_err(|err| Error::Internal(format!("Failed to parse {}: {}", filepath, err)))
+    }
+
+    /// Finds the settings for a pool, preferring the longest matching url prefix.
+    pub fn find(&self, pool_id: &str) -> Option<&PoolAuth> {
+        let pool_id = pool_id.trim_end_matches('/');
+        self.0
+            .iter()
+            .filter(|(key, _)| pool_id.starts_with(key.trim_end_matches('/')))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, auth)| auth)
+    }
+}
+
+impl PoolAuth {
+    fn apply(&self, mut builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder, Error> {
+        // Default headers
+        let mut headers = HeaderMap::new();
+        if let Some(ref token) = self.bearer {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| Error::Internal("Invalid pool bearer token".to_string()))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        for (name, value) in self.headers.iter() {
+            let name = HeaderName::from_str(name)
+                .map_err(|_| Error::Internal(format!("Invalid pool header name: {}", name)))?;
+            let mut value = HeaderValue::from_str(value)
+                .map_err(|_| Error::Internal(format!("Invalid value for pool header {}", name)))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        builder = builder.default_headers(headers);
+
+        // Client certificate
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                let identity = Identity::from_pkcs8_pem(&read_pem(cert)?, &read_pem(key)?)
+                    .map_err(|err| {
+                        Error::Internal(format!("Invalid client certificate: {}", err))
+                    })?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Error::Internal(
+                    "Pool client certificate requires both cert and key".to_string(),
+                ))
+            }
+        }
+
+        // Extra root certificate
+        if let Some(ref ca_cert) = self.ca_cert {
+            let certificate = Certificate::from_pem(&read_pem(ca_cert)?)
+                .map_err(|err| Error::Internal(format!("Invalid CA certificate: {}", err)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        Ok(builder)
+    }
+}
+
+fn read_pem(filepath: &str) -> Result<Vec<u8>, Error> {
+    std::fs::read(filepath)
+        .map_err(|err| Error::Internal(format!("Failed to read {}: {}", filepath, err)))
 }
 
 impl Pool {
+    /// Builds a pool client for a url, probing the server once to pick the
+    /// endpoints it supports.
+    pub async fn new(
+        pool_url: &str,
+        auth: Option<&PoolAuth>,
+        proxy: Option<&str>,
+    ) -> Result<Pool, Error> {
+        let mut builder = utils::http_client_builder(proxy)?
+            .connect_timeout(POOL_CONNECT_TIMEOUT)
+            .timeout(POOL_REQUEST_TIMEOUT);
+        if let Some(auth) = auth {
+            builder = auth.apply(builder)?;
+        }
+        let http_client = builder.build()?;
+        let mut pool = Pool {
+            http_client,
+            pool_url: pool_url.to_string(),
+            endpoints: PoolEndpoints::CURRENT,
+            mock: false,
+        };
+        pool.endpoints = pool.probe_endpoints().await?;
+        Ok(pool)
+    }
+
+    /// Marks the pool as a mock pool, after checking the server reports the
+    /// mock pool address.
+    pub async fn into_mock(mut self) -> Result<Pool, Error> {
+        let address = self.get_pool_address().await?.address;
+        if address != mock_pool_address() {
+            return Err(Error::PoolAddressMismatch(mock_pool_address(), address));
+        }
+        self.mock = true;
+        Ok(self)
+    }
+
+    /// Builds a pool client from either a pool url or an on-chain pool address.
+    /// Addresses are resolved to the url stored on-chain, and the server must
+    /// report the same address before it is trusted.
+    pub async fn connect(miner: &Miner, pool_id: &str) -> Result<Pool, Error> {
+        let auth_config = PoolAuthConfig::load(miner.pool_auth_filepath.as_deref())?;
+        let Ok(address) = Pubkey::from_str(pool_id) else {
+            return Pool::new(pool_id, auth_config.find(pool_id), miner.proxy.as_deref()).await;
+        };
+
+        // Lookup pool url from the address
+        let pool_account = get_pool(&miner.rpc_client, address)
+            .await
+            .map_err(|err| Error::Internal(format!("Failed to fetch pool {}: {}", address, err)))?;
+        let pool_url = pool_url_from_account(&pool_account);
+        if pool_url.is_empty() {
+            return Err(Error::Internal(format!(
+                "Pool {} has no url on-chain",
+                address
+            )));
+        }
+        let auth = auth_config
+            .find(pool_id)
+            .or_else(|| auth_config.find(&pool_url));
+        let pool = Pool::new(&pool_url, auth, miner.proxy.as_deref()).await?;
+
+        // Check the server speaks for this pool
+        let server_address = pool.get_pool_address().await?.address;
+        if server_address != address {
+            return Err(Error::PoolAddressMismatch(address, server_address));
+        }
+        Ok(pool)
+    }
+
+    /// Servers predating `/address` expose `/pool-address` and `/update-balance`.
+    async fn probe_endpoints(&self) -> Result<PoolEndpoints, PoolError> {
+        for endpoints in [PoolEndpoints::CURRENT, PoolEndpoints::LEGACY] {
+            let get_url = format!("{}/{}", self.pool_url(), endpoints.address);
+            match self
+                .request::<PoolAddress>(self.http_client.get(get_url))
+                .await
+            {
+                Ok(_) => return Ok(endpoints),
+                Err(PoolError::NotFound) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(PoolError::Decode(
+            "server exposes neither /address nor /pool-address".to_string(),
+        ))
+    }
+
+    /// Sends a request and decodes the json response, mapping failures to
+    /// typed pool errors.
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, PoolError> {
+        let resp = self.send(request).await?;
+        resp.json::<T>().await.map_err(From::from)
+    }
+
+    /// Like `request`, but for endpoints keyed by the member's authority, where
+    /// a 404 means the member is not registered.
+    async fn member_request<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, PoolError> {
+        let resp = self.member_send(request).await?;
+        resp.json::<T>().await.map_err(From::from)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Response, PoolError> {
+        let resp = request.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(PoolError::from_status(status));
+        }
+        Ok(resp)
+    }
+
+    async fn member_send(&self, request: reqwest::RequestBuilder) -> Result<Response, PoolError> {
+        self.send(request).await.map_err(|err| match err {
+            PoolError::NotFound => PoolError::MemberNotFound,
+            err => err,
+        })
+    }
+
     pub async fn post_pool_register(&self, miner: &Miner) -> Result<Member, Error> {
         let pubkey = miner.signer().pubkey();
         let post_url = format!("{}/register", self.pool_url());
         // check if on-chain member account exists already
         let pool_pda = self.get_pool_address().await?;
-        if let Err(_err) = self.get_pool_member_onchain(miner, pool_pda.address).await {
+        if self.mock {
+            // mock pools have no on-chain accounts
+            println!(
+                "{} Mock pool {}, skipping member account creation",
+                "WARNING".bold().yellow(),
+                pool_pda.address
+            );
+        } else if let Err(_err) = self.get_pool_member_onchain(miner, pool_pda.address).await {
             // on-chain member account not found
             // create one before submitting register payload to pool
             let ix = ore_pool_api::sdk::join(pubkey, pool_pda.address, pubkey);
@@ -239,34 +850,18 @@ impl Pool {
         // submit idempotent register payload
         // will simply return off-chain account if already registered
         let body = RegisterPayload { authority: pubkey };
-        let resp = self.http_client.post(post_url).json(&body).send().await?;
-        match resp.error_for_status() {
-            Err(err) => {
-                println!("{:?}", err);
-                Err(err).map_err(From::from)
-            }
-            Ok(resp) => resp.json::<Member>().await.map_err(From::from),
-        }
+        let member = self
+            .request::<Member>(self.http_client.post(post_url).json(&body))
+            .await?;
+        Ok(member)
     }
 
     pub async fn get_pool_address(&self) -> Result<PoolAddress, Error> {
-        let get_url = format!("{}/address", self.pool_url());
-        let resp = self.http_client.get(get_url).send().await?;
-        match resp.error_for_status() {
-            Err(_err) => {
-                // Backwards compatibility
-                let get_url = format!("{}/pool-address", self.pool_url());
-                let resp = self.http_client.get(get_url).send().await?;
-                match resp.error_for_status() {
-                    Err(err) => {
-                        println!("{:?}", err);
-                        Err(err).map_err(From::from)
-                    }
-                    Ok(resp) => resp.json::<PoolAddress>().await.map_err(From::from),
-                }
-            }
-            Ok(resp) => resp.json::<PoolAddress>().await.map_err(From::from),
-        }
+        let get_url = format!("{}/{}", self.pool_url(), self.endpoints.address);
+        let address = self
+            .request::<PoolAddress>(self.http_client.get(get_url))
+            .await?;
+        Ok(address)
     }
 
     pub async fn get_pool_member_onchain(
@@ -281,20 +876,28 @@ impl Pool {
         Ok(*pool)
     }
 
+    /// Rewards the pool server has attributed to the member off-chain, but which
+    /// have not yet been committed to the on-chain member account.
+    pub async fn get_pending_rewards(
+        &self,
+        miner: &Miner,
+        pool_address: Pubkey,
+    ) -> Result<u64, Error> {
+        let member_onchain = self.get_pool_member_onchain(miner, pool_address).await?;
+        let member_offchain = self.get_pool_member(miner).await?;
+        Ok((member_offchain.total_balance as u64).saturating_sub(member_onchain.total_balance))
+    }
+
     pub async fn get_pool_member(&self, miner: &Miner) -> Result<Member, Error> {
         let pubkey = miner.signer().pubkey();
         let get_url = format!("{}/member/{}", self.pool_url(), pubkey);
-        let resp = self.http_client.get(get_url).send().await?;
-        match resp.error_for_status() {
-            Err(err) => {
-                println!("{:?}", err);
-                Err(err).map_err(From::from)
-            }
-            Ok(resp) => resp.json::<Member>().await.map_err(From::from),
-        }
+        let member = self
+            .member_request::<Member>(self.http_client.get(get_url))
+            .await?;
+        Ok(member)
     }
 
-    pub async fn _get_staker_onchain(
+    pub async fn get_staker_onchain(
         &self,
         miner: &Miner,
         pool_address: Pubkey,
@@ -316,14 +919,25 @@ impl Pool {
         let progress_bar = Arc::new(spinner::new_progress_bar());
         loop {
             progress_bar.set_message(format!("Fetching new challenge... (retry {})", retries));
-            let challenge = self.get_pool_challenge(miner).await?;
-            if challenge.challenge.lash_hash_at == last_hash_at {
-                retries += 1;
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            } else {
-                progress_bar.finish_with_message("Found new challenge");
-                return Ok(challenge);
+            match self.get_pool_challenge(miner).await {
+                Ok(challenge) if challenge.challenge.lash_hash_at != last_hash_at => {
+                    progress_bar.finish_with_message("Found new challenge");
+                    return Ok(challenge);
+                }
+                Ok(_) => {}
+                Err(Error::Pool(err)) if err.is_transient() => {
+                    progress_bar.set_message(format!(
+                        "Fetching new challenge... (retry {}, {})",
+                        retries, err
+                    ));
+                }
+                Err(err) => {
+                    progress_bar.finish_and_clear();
+                    return Err(err);
+                }
             }
+            retries += 1;
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
         }
     }
 
@@ -338,29 +952,27 @@ impl Pool {
         progress_bar.set_message(format!("Fetching collecting event... (retry {})", attempts));
         loop {
             // Parse pool event
-            let resp = self.http_client.get(get_url.clone()).send().await?;
-            match resp.error_for_status() {
-                Err(err) => {
-                    if let Some(status) = err.status() {
-                        match status {
-                            StatusCode::NOT_FOUND | StatusCode::BAD_GATEWAY => {
-                                // No op. Retry.
-                            }
-                            _ => {
-                                progress_bar.finish_and_clear();
-                                return Err(Error::Internal(status.to_string()))
-                                    .map_err(From::from);
-                            }
-                        }
+            match self
+                .request::<ore_pool_types::PoolMemberMiningEvent>(
+                    self.http_client.get(get_url.clone()),
+                )
+                .await
+            {
+                Ok(event) => {
+                    if event.last_hash_at >= last_hash_at {
+                        progress_bar.finish_and_clear();
+                        return Ok(event);
                     }
                 }
-                Ok(resp) => {
-                    if let Ok(event) = resp.json::<ore_pool_types::PoolMemberMiningEvent>().await {
-                        if event.last_hash_at as i64 >= last_hash_at {
-                            progress_bar.finish_and_clear();
-                            return Ok(event);
-                        }
-                    }
+                Err(PoolError::NotFound | PoolError::Decode(_)) => {
+                    // Event not published yet. Retry.
+                }
+                Err(err) if err.is_transient() => {
+                    // No op. Retry.
+                }
+                Err(err) => {
+                    progress_bar.finish_and_clear();
+                    return Err(err.into());
                 }
             }
 
@@ -368,8 +980,7 @@ impl Pool {
             attempts += 1;
             if attempts > 10 {
                 progress_bar.finish_with_message("Retry limit exceeded");
-                return Err(Error::Internal("Retry limit exceeded".to_string()))
-                    .map_err(From::from);
+                return Err(Error::Internal("Retry limit exceeded".to_string()));
             }
             progress_bar.set_message(format!("Fetching collecting event... (retry {})", attempts));
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
@@ -404,7 +1015,7 @@ impl Pool {
             Some(signer_pubkey),
         );
         let hash = miner.rpc_client.get_latest_blockhash().await?;
-        tx.partial_sign(&[signer], hash);
+        tx.try_partial_sign(&[signer], hash)?;
 
         // build payload
         let paylaod = UpdateBalancePayload {
@@ -414,54 +1025,23 @@ impl Pool {
         };
 
         // post
-        let post_url = format!("{}/commit", self.pool_url());
+        let post_url = format!("{}/{}", self.pool_url(), self.endpoints.commit);
         let resp = self
-            .http_client
-            .post(post_url)
-            .json(&paylaod)
-            .send()
+            .member_send(self.http_client.post(post_url).json(&paylaod))
             .await?;
-        match resp.error_for_status() {
-            Err(_err) => {
-                // Backwards compatibility
-                let post_url = format!("{}/update-balance", self.pool_url());
-                let resp = self
-                    .http_client
-                    .post(post_url)
-                    .json(&paylaod)
-                    .send()
-                    .await?;
-                match resp.error_for_status() {
-                    Err(err) => {
-                        println!("{:?}", err);
-                        Err(err).map_err(From::from)
-                    }
-                    Ok(resp) => {
-                        let balance_update = resp.json::<BalanceUpdate>().await;
-                        println!("{:?}", balance_update);
-                        Ok(())
-                    }
-                }
-            }
-            Ok(resp) => {
-                let balance_update = resp.json::<BalanceUpdate>().await;
-                println!("{:?}", balance_update);
-                Ok(())
-            }
-        }
+        resp.json::<BalanceUpdate>()
+            .await
+            .map_err(PoolError::from)?;
+        Ok(())
     }
 
     async fn get_pool_challenge(&self, miner: &Miner) -> Result<MemberChallenge, Error> {
         let pubkey = miner.signer().pubkey();
         let get_url = format!("{}/challenge/{}", self.pool_url(), pubkey);
-        let resp = self.http_client.get(get_url).send().await?;
-        match resp.error_for_status() {
-            Err(err) => {
-                println!("{:?}", err);
-                Err(err).map_err(From::from)
-            }
-            Ok(resp) => resp.json::<MemberChallenge>().await.map_err(From::from),
-        }
+        let challenge = self
+            .member_request::<MemberChallenge>(self.http_client.get(get_url))
+            .await?;
+        Ok(challenge)
     }
 
     pub async fn post_pool_solution(
@@ -470,26 +1050,16 @@ impl Pool {
         solution: &Solution,
     ) -> Result<(), Error> {
         let pubkey = miner.signer().pubkey();
-        let signature = Pool::sign_solution(miner, solution);
+        let signature = Pool::sign_solution(miner, solution)?;
         let payload = ContributePayload {
             authority: pubkey,
             solution: *solution,
             signature,
         };
         let post_url = format!("{}/contribute", self.pool_url());
-        let resp = self
-            .http_client
-            .post(post_url)
-            .json(&payload)
-            .send()
+        self.member_send(self.http_client.post(post_url).json(&payload))
             .await?;
-        match resp.error_for_status() {
-            Err(err) => {
-                println!("{:?}", err);
-                Err(err).map_err(From::from)
-            }
-            Ok(_) => Ok(()),
-        }
+        Ok(())
     }
 
     fn pool_url(&self) -> String {
@@ -497,33 +1067,35 @@ impl Pool {
         pool_url.trim_end_matches('/').to_string()
     }
 
-    fn sign_solution(miner: &Miner, solution: &Solution) -> Signature {
-        let keypair = &miner.signer();
-        keypair.sign_message(solution.to_bytes().as_slice())
+    fn sign_solution(miner: &Miner, solution: &Solution) -> Result<Signature, Error> {
+        let signer = miner.signer();
+        Ok(signer.try_sign_message(solution.to_bytes().as_slice())?)
     }
 }
 
-// async fn parse_pool_id(pool_id: &String) -> Result<Pubkey, Error> {
-//     if let Ok(_address) = Pubkey::from_str(pool_id) {
-//         // Ok(address)
-//         // TODO We need a way to lookup pool url from the address (url must be onchain)
-//         panic!("Not implemented");
-//     } else {
-//         lookup_pool_address(pool_id.clone()).await
-//     }
-// }
-
-// async fn lookup_pool_address(url: String) -> Result<Pubkey, Error> {
-//     let get_url = format!("{}/address", url);
-//     let resp = reqwest::Client::new().get(get_url).send().await?;
-//     match resp.error_for_status() {
-//         Err(err) => {
-//             println!("{:?}", err);
-//             Err(err).map_err(From::from)
-//         }
-//         Ok(resp) => resp.json::<Pubkey>().await.map_err(From::from),
-//     }
-// }
+/// The slice of the nonce space assigned to a pool member. Members search and
+/// pool servers check contributions against the same partitioning.
+pub fn nonce_range(member_id: u64, num_members: u64) -> (u64, u64) {
+    let size = u64::MAX.saturating_div(num_members.max(1));
+    let start = size.saturating_mul(member_id);
+    (start, start.saturating_add(size))
+}
+
+/// Decodes the null-padded url stored in an on-chain pool account.
+pub fn pool_url_from_account(pool: &ore_pool_api::state::Pool) -> String {
+    let url = String::from_utf8(pool.url.to_vec()).unwrap_or_default();
+    url.trim_end_matches('\0').to_string()
+}
+
+#[derive(Clone, Tabled)]
+pub struct ShareTableData {
+    #[tabled(rename = "Mint")]
+    mint: String,
+    #[tabled(rename = "Share")]
+    address: String,
+    #[tabled(rename = "Balance")]
+    balance: String,
+}
 
 #[derive(Clone, Tabled)]
 pub struct PoolTableData {
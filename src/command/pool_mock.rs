@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use ore_pool_api::state::pool_pda;
+use ore_pool_types::PoolMemberMiningEvent;
+use rand::Rng;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::{args::PoolServeMockArgs, error::Error, Miner};
+
+use super::pool_server::{unix_timestamp, PoolServer};
+
+/// Synthetic reward paid out to members each round, split by difficulty.
+const MOCK_ROUND_REWARD: u64 = 1_000_000_000;
+
+/// The address mock pools serve as. Its authority is the all-zero key, which
+/// nobody can sign for, so no real pool can share it. Miners opt into mock
+/// pools with `collect --mock-pool`.
+pub fn mock_pool_address() -> Pubkey {
+    pool_pda(Pubkey::default()).0
+}
+
+impl Miner {
+    pub async fn pool_serve_mock(&self, args: PoolServeMockArgs) -> Result<(), Error> {
+        let (address, bump) = pool_pda(Pubkey::default());
+        let server = Arc::new(PoolServer::new(
+            address,
+            bump,
+            args.num_devices,
+            HashMap::new(),
+            None,
+        ));
+
+        // Hand out a synthetic challenge every interval
+        tokio::spawn({
+            let server = server.clone();
+            async move {
+                loop {
+                    let last_hash_at = unix_timestamp();
+                    let contributions = server.open_round(
+                        rand::thread_rng().gen(),
+                        last_hash_at,
+                        args.min_difficulty,
+                        args.challenge_interval,
+                    );
+                    close_mock_round(&server, contributions, last_hash_at);
+                    tokio::time::sleep(Duration::from_secs(args.challenge_interval)).await;
+                }
+            }
+        });
+
+        server.serve(&args.listen).await
+    }
+}
+
+/// Attributes the synthetic reward for a finished round and publishes events.
+fn close_mock_round(
+    server: &PoolServer,
+    contributions: HashMap<Pubkey, super::pool_server::Contribution>,
+    next_hash_at: i64,
+) {
+    if contributions.is_empty() {
+        return;
+    }
+    let round = server.round.read().unwrap().number - 1;
+    let best_difficulty = contributions
+        .values()
+        .map(|c| c.difficulty)
+        .max()
+        .unwrap_or(0);
+    let rewards = server.attribute_rewards(&contributions, MOCK_ROUND_REWARD);
+    for (authority, contribution) in contributions.iter() {
+        server.publish_event(
+            *authority,
+            PoolMemberMiningEvent {
+                signature: Signature::default(),
+                block: round,
+                timestamp: unix_timestamp() as u64,
+                balance: server.member_balance(authority),
+                difficulty: best_difficulty as u64,
+                last_hash_at: next_hash_at,
+                timing: 0,
+                net_reward: MOCK_ROUND_REWARD,
+                net_base_reward: MOCK_ROUND_REWARD,
+                net_miner_boost_reward: 0,
+                net_staker_boost_reward: 0,
+                member_difficulty: contribution.difficulty as u64,
+                member_reward: rewards.get(authority).copied().unwrap_or(0),
+            },
+        );
+        println!(
+            "  Round {} closed: {} contributed {} solutions, best score {}",
+            round, authority, contribution.count, contribution.difficulty
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use drillx::Solution;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use crate::{
+        command::pool::{nonce_range, Pool},
+        send::FeePayerPool,
+        signer::DynSigner,
+    };
+
+    use super::*;
+
+    /// A miner that never reaches an RPC node, which mock pools don't need.
+    fn offline_miner(signer: DynSigner) -> Miner {
+        Miner::new(
+            Arc::new(RpcClient::new("http://127.0.0.1:1".to_string())),
+            None,
+            signer.clone(),
+            None,
+            Arc::new(FeePayerPool::new(vec![signer], None, 0.0)),
+            None,
+            None,
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            vec![],
+            Arc::new(RwLock::new(vec![])),
+            Arc::new(RwLock::new(vec![])),
+        )
+    }
+
+    /// Serves a pool on a free local port and returns a client for it.
+    async fn serve(server: Arc<PoolServer>) -> Pool {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let listen = format!("127.0.0.1:{}", port);
+        tokio::spawn({
+            let listen = listen.clone();
+            async move { server.serve(&listen).await }
+        });
+        let url = format!("http://{}", listen);
+        for _ in 0..50 {
+            if let Ok(pool) = Pool::new(&url, None, None).await {
+                return pool;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("pool server did not come up");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pool_client_collects_with_mock_server() {
+        let (address, bump) = pool_pda(Pubkey::default());
+        let server = Arc::new(PoolServer::new(address, bump, 1, HashMap::new(), None));
+        let last_hash_at = unix_timestamp();
+        server.open_round([7; 32], last_hash_at, 0, 60);
+        let pool = serve(server.clone()).await.into_mock().await.unwrap();
+        assert!(pool.mock);
+
+        // Register without touching the chain
+        let keypair = Arc::new(Keypair::new());
+        let authority = keypair.pubkey();
+        let miner = offline_miner(keypair);
+        let member = pool.post_pool_register(&miner).await.unwrap();
+        assert_eq!(member.authority, authority.to_string());
+
+        // Solve the challenge within the member's nonce range
+        let challenge = pool.get_updated_pool_challenge(&miner, 0).await.unwrap();
+        assert_eq!(challenge.challenge.lash_hash_at, last_hash_at);
+        let (start, _) = nonce_range(member.id as u64, challenge.num_total_members);
+        let solution = (start..)
+            .find_map(|nonce| {
+                let nonce = nonce.to_le_bytes();
+                drillx::hash(&challenge.challenge.challenge, &nonce)
+                    .ok()
+                    .map(|hash| Solution::new(hash.d, nonce))
+            })
+            .unwrap();
+        pool.post_pool_solution(&miner, &solution).await.unwrap();
+
+        // Close the round and read back the member's event
+        let next_hash_at = last_hash_at + 1;
+        let contributions = server.open_round([8; 32], next_hash_at, 0, 60);
+        assert!(contributions.contains_key(&authority));
+        close_mock_round(&server, contributions, next_hash_at);
+        let event = pool
+            .get_latest_pool_event(authority, next_hash_at)
+            .await
+            .unwrap();
+        assert_eq!(event.member_reward, MOCK_ROUND_REWARD);
+        assert_eq!(event.balance, MOCK_ROUND_REWARD);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn into_mock_rejects_real_pools() {
+        let server = Arc::new(PoolServer::new(
+            Pubkey::new_unique(),
+            0,
+            1,
+            HashMap::new(),
+            None,
+        ));
+        let pool = serve(server).await;
+        assert!(!pool.mock);
+        assert!(matches!(
+            pool.into_mock().await,
+            Err(Error::PoolAddressMismatch(..))
+        ));
+    }
+}
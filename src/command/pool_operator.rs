@@ -0,0 +1,400 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use b64::FromBase64;
+use colored::*;
+use eore_api::{event::MineEvent, state::proof_pda};
+use ore_pool_api::state::{member_pda, pool_pda};
+use ore_pool_types::{Member, PoolMemberMiningEvent, UpdateBalancePayload};
+use solana_sdk::{
+    hash::hashv,
+    instruction::{AccountMeta, CompiledInstruction, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+};
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
+
+use crate::{
+    args::PoolOperateArgs,
+    error::Error,
+    utils::{get_config, get_member, get_pool, get_updated_proof_with_authority, ComputeBudget},
+    Miner,
+};
+
+use super::pool_server::{Contribution, PoolServer};
+
+/// Number of attribute instructions packed into a single transaction.
+const ATTRIBUTE_BATCH_SIZE: usize = 8;
+
+impl Miner {
+    pub async fn pool_operate(&self, args: PoolOperateArgs) -> Result<(), Error> {
+        let signer = self.signer();
+        let (pool_address, bump) = pool_pda(signer.pubkey());
+
+        // Launch pool, if needed
+        if get_pool(&self.rpc_client, pool_address).await.is_err() {
+            let url = args.url.clone().ok_or(Error::Internal(
+                "A pool url is required to launch a new pool".to_string(),
+            ))?;
+            println!("Launching pool {} at {}...", pool_address, url);
+            let ix = ore_pool_api::sdk::launch(signer.pubkey(), signer.pubkey(), url)
+                .map_err(|err| Error::Internal(format!("Failed to build launch: {:?}", err)))?;
+            self.send_and_confirm(&[ix], ComputeBudget::Fixed(200_000), false)
+                .await?;
+        }
+
+        // Restore members from the state file
+        let members = load_members(&args.state)?;
+        println!("Loaded {} members from {}", members.len(), args.state);
+        let (commits_tx, mut commits_rx) = tokio::sync::mpsc::unbounded_channel();
+        let server = Arc::new(PoolServer::new(
+            pool_address,
+            bump,
+            args.num_devices,
+            members,
+            Some(commits_tx),
+        ));
+
+        // Land commits requested by members
+        tokio::spawn({
+            let miner = self.clone();
+            let server = server.clone();
+            async move {
+                while let Some(payload) = commits_rx.recv().await {
+                    if let Err(err) = miner.land_member_commit(&server, payload).await {
+                        println!("{} {:?}", "ERROR".bold().red(), err);
+                    }
+                }
+            }
+        });
+
+        // Serve the pool protocol
+        tokio::spawn({
+            let server = server.clone();
+            let listen = args.listen.clone();
+            async move {
+                if let Err(err) = server.serve(&listen).await {
+                    println!("{} {:?}", "ERROR".bold().red(), err);
+                    std::process::exit(1);
+                }
+            }
+        });
+
+        self.operate_rounds(&server, &args).await
+    }
+
+    async fn operate_rounds(
+        &self,
+        server: &PoolServer,
+        args: &PoolOperateArgs,
+    ) -> Result<(), Error> {
+        let signer = self.signer();
+        let pool_address = server.address;
+        let mut last_hash_at = 0;
+        let mut landed: Option<(Signature, u64, i64, MineEvent)> = None;
+        loop {
+            // Wait for the pool proof to update
+            let config = get_config(&self.rpc_client).await;
+            let proof =
+                get_updated_proof_with_authority(&self.rpc_client, pool_address, last_hash_at)
+                    .await
+                    .map_err(|err| Error::Internal(err.to_string()))?;
+            last_hash_at = proof.last_hash_at;
+
+            // Open the next round and settle the previous one
+            let min_difficulty = args.min_difficulty.max(config.min_difficulty);
+            let cutoff_time = self
+                .get_cutoff(proof.last_hash_at, args.buffer_time)
+                .await?;
+            let contributions = server.open_round(
+                proof.challenge,
+                proof.last_hash_at,
+                min_difficulty,
+                cutoff_time,
+            );
+            if let Some((sig, slot, block_time, event)) = landed.take() {
+                self.settle_round(
+                    server,
+                    args,
+                    contributions,
+                    sig,
+                    slot,
+                    block_time,
+                    event,
+                    last_hash_at,
+                );
+                // u64::is_multiple_of needs a newer toolchain than the pinned one
+                let round = server.round.read().unwrap().number;
+                if round.checked_rem(args.attribute_interval.max(1)) == Some(0) {
+                    if let Err(err) = self.attribute_members(server).await {
+                        println!("{} {:?}", "ERROR".bold().red(), err);
+                    }
+                }
+            }
+
+            // Collect contributions until the cutoff, and until one meets the min difficulty
+            tokio::time::sleep(Duration::from_secs(cutoff_time)).await;
+            let (solution, difficulty) = loop {
+                if let Some(best) = server.best_contribution() {
+                    break best;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            };
+
+            // Attest to the contributions backing this solution
+            let attestation = {
+                let round = server.round.read().unwrap();
+                let mut leaves: Vec<[u8; 36]> = round
+                    .contributions
+                    .iter()
+                    .map(|(authority, c)| {
+                        let mut leaf = [0u8; 36];
+                        leaf[..32].copy_from_slice(authority.as_ref());
+                        leaf[32..].copy_from_slice(&c.difficulty.to_le_bytes());
+                        leaf
+                    })
+                    .collect();
+                leaves.sort();
+                let leaves: Vec<&[u8]> = leaves.iter().map(|l| l.as_slice()).collect();
+                hashv(&leaves).to_bytes()
+            };
+
+            // Submit best solution to the pool's proof
+            let mut ixs = vec![eore_api::sdk::auth(proof_pda(pool_address).0)];
+            if self.should_reset(config).await {
+                ixs.push(eore_api::sdk::reset(signer.pubkey()));
+            }
+            ixs.push(ore_pool_api::sdk::submit(
+                signer.pubkey(),
+                solution,
+                attestation,
+                self.find_bus().await,
+            ));
+            println!("Submitting solution with score {}...", difficulty);
+            match self
+                .send_and_confirm_mining(&ixs, ComputeBudget::Dynamic, false)
+                .await
+            {
+                Ok(sig) => match self.get_mine_event(sig).await {
+                    Ok((slot, block_time, event)) => landed = Some((sig, slot, block_time, event)),
+                    Err(err) => println!("{} {:?}", "ERROR".bold().red(), err),
+                },
+                Err(err) => {
+                    // Reopen the current challenge, since the proof will not advance
+                    println!("{} {}", "ERROR".bold().red(), err);
+                    last_hash_at = 0;
+                }
+            }
+        }
+    }
+
+    /// Splits the landed reward across contributors, publishes their events and
+    /// persists the updated member balances.
+    #[allow(clippy::too_many_arguments)]
+    fn settle_round(
+        &self,
+        server: &PoolServer,
+        args: &PoolOperateArgs,
+        contributions: HashMap<Pubkey, Contribution>,
+        sig: Signature,
+        slot: u64,
+        block_time: i64,
+        event: MineEvent,
+        last_hash_at: i64,
+    ) {
+        let operator_fee = event.net_reward.saturating_mul(args.operator_fee.min(100)) / 100;
+        let rewards = server.attribute_rewards(
+            &contributions,
+            event.net_reward.saturating_sub(operator_fee),
+        );
+        for (authority, contribution) in contributions.iter() {
+            server.publish_event(
+                *authority,
+                PoolMemberMiningEvent {
+                    signature: sig,
+                    block: slot,
+                    timestamp: block_time as u64,
+                    balance: server.member_balance(authority),
+                    difficulty: event.difficulty,
+                    last_hash_at,
+                    timing: event.timing,
+                    net_reward: event.net_reward,
+                    net_base_reward: event.net_base_reward,
+                    net_miner_boost_reward: event.net_miner_boost_reward,
+                    net_staker_boost_reward: event.net_staker_boost_reward,
+                    member_difficulty: contribution.difficulty as u64,
+                    member_reward: rewards.get(authority).copied().unwrap_or(0),
+                },
+            );
+        }
+        println!(
+            "{} Round settled: {} members rewarded {} BITZ",
+            "OK".bold().green(),
+            contributions.len(),
+            crate::utils::amount_u64_to_string(event.net_reward)
+        );
+        if let Err(err) = save_members(&args.state, server) {
+            println!("{} {:?}", "ERROR".bold().red(), err);
+        }
+    }
+
+    /// Commits every member whose off-chain balance is ahead of its on-chain account.
+    async fn attribute_members(&self, server: &PoolServer) -> Result<(), Error> {
+        let signer = self.signer();
+        let members: Vec<(Pubkey, u64)> = server
+            .members
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(authority, member)| (*authority, member.total_balance as u64))
+            .collect();
+        let mut ixs = vec![];
+        for (authority, total_balance) in members {
+            let member_address = member_pda(authority, server.address).0;
+            let Ok(member) = get_member(&self.rpc_client, member_address).await else {
+                continue;
+            };
+            if member.total_balance < total_balance {
+                ixs.push(ore_pool_api::sdk::attribute(
+                    signer.pubkey(),
+                    authority,
+                    total_balance,
+                ));
+            }
+        }
+        for batch in ixs.chunks(ATTRIBUTE_BATCH_SIZE) {
+            self.send_and_confirm(
+                batch,
+                ComputeBudget::Fixed(20_000 * batch.len() as u32),
+                false,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Countersigns a member's attribute transaction after checking it commits
+    /// exactly the balance the pool owes them.
+    async fn land_member_commit(
+        &self,
+        server: &PoolServer,
+        payload: UpdateBalancePayload,
+    ) -> Result<(), Error> {
+        let signer = self.signer();
+        let total_balance = server
+            .members
+            .read()
+            .unwrap()
+            .get(&payload.authority)
+            .map(|member| member.total_balance as u64)
+            .ok_or(Error::Internal("Unknown member".to_string()))?;
+        let expected =
+            ore_pool_api::sdk::attribute(signer.pubkey(), payload.authority, total_balance);
+        let mut tx = payload.transaction;
+
+        // Only the expected attribute instruction and compute budget settings may be signed
+        let instructions: Vec<Instruction> = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ix| decompile_instruction(&tx.message, ix))
+            .collect();
+        let num_expected = instructions.iter().filter(|ix| **ix == expected).count();
+        let is_expected = num_expected == 1
+            && instructions.iter().all(|ix| {
+                *ix == expected
+                    || (ix.program_id == solana_sdk::compute_budget::ID && ix.accounts.is_empty())
+            });
+        if !is_expected {
+            return Err(Error::Internal(format!(
+                "Rejected commit from {}: unexpected transaction",
+                payload.authority
+            )));
+        }
+        tx.try_partial_sign(&[&signer], payload.hash)
+            .map_err(|err| Error::Internal(err.to_string()))?;
+        let sig = self.rpc_client.send_and_confirm_transaction(&tx).await?;
+        println!("Committed balance for {}: {}", payload.authority, sig);
+        Ok(())
+    }
+
+    async fn get_mine_event(&self, sig: Signature) -> Result<(u64, i64, MineEvent), Error> {
+        for _ in 0..30 {
+            if let Ok(tx) = self
+                .rpc_client
+                .get_transaction(&sig, UiTransactionEncoding::Json)
+                .await
+            {
+                if let Some(meta) = tx.transaction.meta {
+                    if let OptionSerializer::Some(log_messages) = meta.log_messages {
+                        let prefix = format!("Program return: {} ", eore_api::ID);
+                        if let Some(return_data) = log_messages
+                            .iter()
+                            .find_map(|log| log.strip_prefix(&prefix))
+                        {
+                            if let Ok(return_data) = return_data.from_base64() {
+                                let event = *MineEvent::from_bytes(&return_data);
+                                return Ok((tx.slot, tx.block_time.unwrap_or_default(), event));
+                            }
+                        }
+                    }
+                }
+                return Err(Error::Internal(format!("No mine event in {}", sig)));
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Err(Error::Internal(format!(
+            "Failed to fetch transaction {}",
+            sig
+        )))
+    }
+}
+
+/// Rebuilds an instruction from a compiled message, with the signer and writable
+/// flags the runtime will apply.
+fn decompile_instruction(message: &Message, ix: &CompiledInstruction) -> Instruction {
+    let accounts = ix
+        .accounts
+        .iter()
+        .map(|&index| {
+            let index = index as usize;
+            AccountMeta {
+                pubkey: message.account_keys[index],
+                is_signer: message.is_signer(index),
+                is_writable: message.is_maybe_writable(index, None),
+            }
+        })
+        .collect();
+    Instruction {
+        program_id: message.account_keys[ix.program_id_index as usize],
+        accounts,
+        data: ix.data.clone(),
+    }
+}
+
+fn load_members(path: &str) -> Result<HashMap<Pubkey, Member>, Error> {
+    if !std::path::Path::new(path).exists() {
+        return Ok(HashMap::new());
+    }
+    let data = std::fs::read_to_string(path)
+        .map_err(|err| Error::Internal(format!("Failed to read {}: {}", path, err)))?;
+    let members: Vec<Member> = serde_json::from_str(&data)
+        .map_err(|err| Error::Internal(format!("Failed to parse {}: {}", path, err)))?;
+    members
+        .into_iter()
+        .map(|member| -> Result<(Pubkey, Member), Error> {
+            Ok((Pubkey::from_str(&member.authority)?, member))
+        })
+        .collect()
+}
+
+fn save_members(path: &str, server: &PoolServer) -> Result<(), Error> {
+    let members = server.members.read().unwrap();
+    let mut members: Vec<&Member> = members.values().collect();
+    members.sort_by_key(|member| member.id);
+    let data =
+        serde_json::to_string_pretty(&members).map_err(|err| Error::Internal(err.to_string()))?;
+    std::fs::write(path, data)
+        .map_err(|err| Error::Internal(format!("Failed to write {}: {}", path, err)))
+}
@@ -0,0 +1,488 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use colored::*;
+use drillx::Solution;
+use ore_pool_api::state::member_pda;
+use ore_pool_types::{
+    Challenge, ContributePayload, Member, MemberChallenge, PoolAddress, PoolMemberMiningEvent,
+    RegisterPayload, UpdateBalancePayload,
+};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::error::Error;
+
+use super::pool::nonce_range;
+
+/// Shared state behind the pool HTTP protocol consumed by the `Pool` client.
+/// Drivers (mock or operator) open and close rounds; the HTTP layer hands out
+/// challenges and records contributions.
+///
+/// Whenever both are held, `round` is locked before `members`.
+pub struct PoolServer {
+    pub address: Pubkey,
+    pub bump: u8,
+    pub num_devices: u8,
+    pub round: RwLock<Round>,
+    pub members: RwLock<HashMap<Pubkey, Member>>,
+    pub events: RwLock<HashMap<Pubkey, Arc<PoolMemberMiningEvent>>>,
+    pub commits: Option<UnboundedSender<UpdateBalancePayload>>,
+}
+
+pub struct Round {
+    pub number: u64,
+    pub challenge: [u8; 32],
+    pub last_hash_at: i64,
+    pub min_difficulty: u64,
+    pub cutoff_time: u64,
+    pub contributions: HashMap<Pubkey, Contribution>,
+}
+
+pub struct Contribution {
+    pub solution: Solution,
+    pub difficulty: u32,
+    pub count: u64,
+}
+
+impl PoolServer {
+    pub fn new(
+        address: Pubkey,
+        bump: u8,
+        num_devices: u8,
+        members: HashMap<Pubkey, Member>,
+        commits: Option<UnboundedSender<UpdateBalancePayload>>,
+    ) -> Self {
+        Self {
+            address,
+            bump,
+            num_devices,
+            round: RwLock::new(Round {
+                number: 0,
+                challenge: [0; 32],
+                last_hash_at: 0,
+                min_difficulty: u64::MAX,
+                cutoff_time: 0,
+                contributions: HashMap::new(),
+            }),
+            members: RwLock::new(members),
+            events: RwLock::new(HashMap::new()),
+            commits,
+        }
+    }
+
+    /// Starts a new round, returning the contributions to the previous one.
+    /// Reopening the current challenge, as after a failed submit, keeps the
+    /// round and its contributions and returns none.
+    pub fn open_round(
+        &self,
+        challenge: [u8; 32],
+        last_hash_at: i64,
+        min_difficulty: u64,
+        cutoff_time: u64,
+    ) -> HashMap<Pubkey, Contribution> {
+        let mut round = self.round.write().unwrap();
+        if round.challenge == challenge && round.last_hash_at == last_hash_at {
+            round.min_difficulty = min_difficulty;
+            round.cutoff_time = cutoff_time;
+            return HashMap::new();
+        }
+        let contributions = std::mem::take(&mut round.contributions);
+        *round = Round {
+            number: round.number + 1,
+            challenge,
+            last_hash_at,
+            min_difficulty,
+            cutoff_time,
+            contributions: HashMap::new(),
+        };
+        contributions
+    }
+
+    /// The best solution submitted to the current round.
+    pub fn best_contribution(&self) -> Option<(Solution, u32)> {
+        let round = self.round.read().unwrap();
+        round
+            .contributions
+            .values()
+            .max_by_key(|c| c.difficulty)
+            .map(|c| (c.solution, c.difficulty))
+    }
+
+    /// Splits a reward across contributors, weighted by 2^difficulty, credits
+    /// each member's off-chain balance and returns the individual rewards.
+    pub fn attribute_rewards(
+        &self,
+        contributions: &HashMap<Pubkey, Contribution>,
+        reward: u64,
+    ) -> HashMap<Pubkey, u64> {
+        let total_weight: u128 = contributions
+            .values()
+            .map(|c| 1u128 << c.difficulty.min(127))
+            .sum();
+        let mut members = self.members.write().unwrap();
+        let mut rewards = HashMap::new();
+        for (authority, contribution) in contributions.iter() {
+            let weight = 1u128 << contribution.difficulty.min(127);
+            let member_reward = (reward as u128 * weight / total_weight.max(1)) as u64;
+            if let Some(member) = members.get_mut(authority) {
+                member.total_balance += member_reward as i64;
+            }
+            rewards.insert(*authority, member_reward);
+        }
+        rewards
+    }
+
+    /// The off-chain balance of a member, or zero if it isn't registered.
+    pub fn member_balance(&self, authority: &Pubkey) -> u64 {
+        self.members
+            .read()
+            .unwrap()
+            .get(authority)
+            .map_or(0, |member| member.total_balance.max(0) as u64)
+    }
+
+    pub fn publish_event(&self, authority: Pubkey, event: PoolMemberMiningEvent) {
+        self.events
+            .write()
+            .unwrap()
+            .insert(authority, Arc::new(event));
+    }
+
+    pub async fn serve(self: Arc<Self>, listen: &str) -> Result<(), Error> {
+        let addr = SocketAddr::from_str(listen)
+            .map_err(|err| Error::Internal(format!("Invalid listen address: {}", err)))?;
+        let app = Router::new()
+            .route("/address", get(address))
+            .route("/register", post(register))
+            .route("/member/:authority", get(member))
+            .route("/challenge/:authority", get(challenge))
+            .route("/contribute", post(contribute))
+            .route("/event/latest/:authority", get(latest_event))
+            .route("/commit", post(commit))
+            .with_state(self.clone());
+        println!(
+            "{} Pool {} listening on http://{}",
+            "INFO".bold().green(),
+            self.address,
+            addr
+        );
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|err| Error::Internal(format!("Failed to bind {}: {}", addr, err)))?;
+        axum::serve(listener, app)
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))
+    }
+}
+
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+async fn address(State(pool): State<Arc<PoolServer>>) -> Json<PoolAddress> {
+    Json(PoolAddress {
+        address: pool.address,
+        bump: pool.bump,
+    })
+}
+
+async fn register(
+    State(pool): State<Arc<PoolServer>>,
+    Json(payload): Json<RegisterPayload>,
+) -> Json<Member> {
+    let mut members = pool.members.write().unwrap();
+    let id = members.len() as i64;
+    let member = members
+        .entry(payload.authority)
+        .or_insert_with(|| Member {
+            address: member_pda(payload.authority, pool.address).0.to_string(),
+            id,
+            authority: payload.authority.to_string(),
+            pool_address: pool.address.to_string(),
+            total_balance: 0,
+            is_approved: true,
+            is_kyc: false,
+            is_synced: true,
+        })
+        .clone();
+    Json(member)
+}
+
+async fn member(
+    State(pool): State<Arc<PoolServer>>,
+    Path(authority): Path<String>,
+) -> Result<Json<Member>, StatusCode> {
+    let authority = Pubkey::from_str(&authority).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let members = pool.members.read().unwrap();
+    members
+        .get(&authority)
+        .cloned()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[allow(deprecated)]
+async fn challenge(
+    State(pool): State<Arc<PoolServer>>,
+    Path(authority): Path<String>,
+) -> Result<Json<MemberChallenge>, StatusCode> {
+    let authority = Pubkey::from_str(&authority).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let round = pool.round.read().unwrap();
+    let members = pool.members.read().unwrap();
+    if !members.contains_key(&authority) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if round.number == 0 {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    Ok(Json(MemberChallenge {
+        challenge: Challenge {
+            challenge: round.challenge,
+            lash_hash_at: round.last_hash_at,
+            min_difficulty: round.min_difficulty,
+            cutoff_time: round.cutoff_time,
+        },
+        num_total_members: members.len() as u64,
+        device_id: 0,
+        num_devices: pool.num_devices,
+        unix_timestamp: unix_timestamp(),
+    }))
+}
+
+async fn contribute(
+    State(pool): State<Arc<PoolServer>>,
+    Json(payload): Json<ContributePayload>,
+) -> StatusCode {
+    // Verify the solution was signed by the member
+    if !payload.signature.verify(
+        &payload.authority.to_bytes(),
+        payload.solution.to_bytes().as_slice(),
+    ) {
+        println!(
+            "  {} Invalid solution signature from {}",
+            "WARNING".bold().yellow(),
+            payload.authority
+        );
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // Look up member
+    let mut round = pool.round.write().unwrap();
+    let members = pool.members.read().unwrap();
+    let Some(member) = members.get(&payload.authority) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    // Verify the solution against the current challenge
+    if !payload.solution.is_valid(&round.challenge) {
+        println!(
+            "  {} Invalid solution from {}",
+            "WARNING".bold().yellow(),
+            payload.authority
+        );
+        return StatusCode::BAD_REQUEST;
+    }
+    let difficulty = payload.solution.to_hash().difficulty();
+    if (difficulty as u64) < round.min_difficulty {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    // Check the nonce falls within the member's slice of the nonce space
+    let nonce = u64::from_le_bytes(payload.solution.n);
+    let (start, end) = nonce_range(member.id as u64, members.len() as u64);
+    if nonce < start || nonce >= end {
+        println!(
+            "  {} Nonce {} from {} is outside its range [{}, {})",
+            "WARNING".bold().yellow(),
+            nonce,
+            payload.authority,
+            start,
+            end
+        );
+        return StatusCode::BAD_REQUEST;
+    }
+
+    // Record best contribution
+    let contribution = round
+        .contributions
+        .entry(payload.authority)
+        .or_insert(Contribution {
+            solution: payload.solution,
+            difficulty,
+            count: 0,
+        });
+    contribution.count += 1;
+    if difficulty > contribution.difficulty {
+        contribution.difficulty = difficulty;
+        contribution.solution = payload.solution;
+    }
+    StatusCode::OK
+}
+
+async fn latest_event(
+    State(pool): State<Arc<PoolServer>>,
+    Path(authority): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let authority = Pubkey::from_str(&authority).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let event = pool
+        .events
+        .read()
+        .unwrap()
+        .get(&authority)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    serde_json::to_value(event.as_ref())
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn commit(
+    State(pool): State<Arc<PoolServer>>,
+    Json(payload): Json<UpdateBalancePayload>,
+) -> Result<Json<Value>, StatusCode> {
+    let (address, balance) = {
+        let members = pool.members.read().unwrap();
+        let member = members
+            .get(&payload.authority)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        (member.address.clone(), member.total_balance)
+    };
+    match pool.commits {
+        Some(ref commits) => commits
+            .send(payload)
+            .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?,
+        None => println!(
+            "  Commit requested by {} for {} (not landed, no chain attached)",
+            payload.authority, balance
+        ),
+    }
+    Ok(Json(json!({
+        "address": address,
+        "balance": balance,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(authority: Pubkey, id: i64) -> Member {
+        Member {
+            address: String::new(),
+            id,
+            authority: authority.to_string(),
+            pool_address: String::new(),
+            total_balance: 0,
+            is_approved: true,
+            is_kyc: false,
+            is_synced: true,
+        }
+    }
+
+    fn contribution(difficulty: u32) -> Contribution {
+        Contribution {
+            solution: Solution::new([difficulty as u8; 16], [0; 8]),
+            difficulty,
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn nonce_ranges_partition_the_nonce_space() {
+        let num_members = 3;
+        let ranges: Vec<(u64, u64)> = (0..num_members)
+            .map(|id| nonce_range(id, num_members))
+            .collect();
+        assert_eq!(ranges[0].0, 0);
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        assert!(u64::MAX - ranges[2].1 < num_members);
+
+        // No members still hands out a range
+        assert_eq!(nonce_range(0, 0), (0, u64::MAX));
+    }
+
+    #[test]
+    fn best_contribution_has_the_highest_difficulty() {
+        let server = PoolServer::new(Pubkey::new_unique(), 0, 1, HashMap::new(), None);
+        assert!(server.best_contribution().is_none());
+        server.open_round([1; 32], 0, 8, 60);
+        {
+            let mut round = server.round.write().unwrap();
+            round
+                .contributions
+                .insert(Pubkey::new_unique(), contribution(12));
+            round
+                .contributions
+                .insert(Pubkey::new_unique(), contribution(17));
+            round
+                .contributions
+                .insert(Pubkey::new_unique(), contribution(9));
+        }
+        let (solution, difficulty) = server.best_contribution().unwrap();
+        assert_eq!(difficulty, 17);
+        assert_eq!(solution.d, [17; 16]);
+
+        // Opening the next round hands back the contributions
+        let contributions = server.open_round([2; 32], 60, 8, 60);
+        assert_eq!(contributions.len(), 3);
+        assert!(server.best_contribution().is_none());
+    }
+
+    #[test]
+    fn reopening_a_challenge_keeps_its_contributions() {
+        let server = PoolServer::new(Pubkey::new_unique(), 0, 1, HashMap::new(), None);
+        server.open_round([1; 32], 60, 8, 60);
+        server
+            .round
+            .write()
+            .unwrap()
+            .contributions
+            .insert(Pubkey::new_unique(), contribution(12));
+
+        // A failed submit reopens the same challenge
+        assert!(server.open_round([1; 32], 60, 8, 30).is_empty());
+        assert_eq!(server.best_contribution().unwrap().1, 12);
+        assert_eq!(server.round.read().unwrap().number, 1);
+        assert_eq!(server.round.read().unwrap().cutoff_time, 30);
+
+        // The next challenge settles them
+        assert_eq!(server.open_round([2; 32], 120, 8, 60).len(), 1);
+    }
+
+    #[test]
+    fn attribute_rewards_weights_by_difficulty() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let members = HashMap::from([(a, member(a, 0)), (b, member(b, 1))]);
+        let server = PoolServer::new(Pubkey::new_unique(), 0, 1, members, None);
+        let contributions = HashMap::from([(a, contribution(10)), (b, contribution(11))]);
+
+        // Twice the difficulty weight, twice the reward
+        let rewards = server.attribute_rewards(&contributions, 3_000);
+        assert_eq!(rewards[&a], 1_000);
+        assert_eq!(rewards[&b], 2_000);
+        assert_eq!(server.member_balance(&a), 1_000);
+        assert_eq!(server.member_balance(&b), 2_000);
+
+        // Balances accumulate across rounds
+        server.attribute_rewards(&contributions, 3_000);
+        assert_eq!(server.member_balance(&b), 4_000);
+    }
+}
@@ -17,8 +17,11 @@ use tabled::{
 };
 
 use crate::{
-    args::{StakeArgs, StakeClaimArgs, StakeCommand, StakeDepositArgs, StakeWithdrawArgs},
+    args::{
+        OfflineArgs, StakeArgs, StakeClaimArgs, StakeCommand, StakeDepositArgs, StakeWithdrawArgs,
+    },
     error::Error,
+    send::required_offline,
     utils::{
         amount_u64_to_f64, format_timestamp, get_boost, get_boost_config, get_boost_stake_accounts,
         get_boosts, get_mint, get_proof_with_authority, get_stake, ComputeBudget, TableData,
@@ -63,31 +66,47 @@ impl Miner {
         let boost_address = boost_pda(mint_address).0;
         let stake_address = stake_pda(pubkey, boost_address).0;
 
-        // Check if boost account exists
-        match get_boost(&self.rpc_client, boost_address).await {
-            Ok(_) => {},
-            Err(err) => {
-                println!("{}: No boost account found for this mint.", "ERROR".bold().red());
-                println!("Make sure you are using a valid mint address that has an associated boost account.");
-                println!("Error details: {}", err);
-                return Err(Error::Internal("Boost account not found".to_string()));
-            }
-        };
-        
-        // Check if stake account exists
-        let stake = match get_stake(&self.rpc_client, stake_address).await {
-            Ok(stake) => stake,
-            Err(err) => {
-                println!("{}: No stake account found for this mint.", "ERROR".bold().red());
-                println!("You may need to deposit funds first before claiming rewards.");
-                println!("Error details: {}", err);
-                return Err(Error::Internal("Stake account not found".to_string()));
+        // Check the boost and stake accounts exist, unless building offline
+        let stake = if claim_args.offline.is_air_gapped() {
+            None
+        } else {
+            // Check if boost account exists
+            match get_boost(&self.rpc_client, boost_address).await {
+                Ok(_) => {},
+                Err(err) => {
+                    println!("{}: No boost account found for this mint.", "ERROR".bold().red());
+                    println!("Make sure you are using a valid mint address that has an associated boost account.");
+                    println!("Error details: {}", err);
+                    return Err(Error::Internal("Boost account not found".to_string()));
+                }
+            };
+
+            // Check if stake account exists
+            match get_stake(&self.rpc_client, stake_address).await {
+                Ok(stake) => Some(stake),
+                Err(err) => {
+                    println!("{}: No stake account found for this mint.", "ERROR".bold().red());
+                    println!("You may need to deposit funds first before claiming rewards.");
+                    println!("Error details: {}", err);
+                    return Err(Error::Internal("Stake account not found".to_string()));
+                }
             }
         };
 
         let mut ixs = vec![];
         let beneficiary = match claim_args.to {
+            None if claim_args.offline.is_offline() => {
+                let (ata, ix) = self.create_ata_ix(pubkey, eore_api::consts::MINT_ADDRESS);
+                ixs.push(ix);
+                ata
+            }
             None => self.initialize_ata(pubkey).await,
+            Some(to) if claim_args.offline.is_offline() => {
+                let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
+                let (ata, ix) = self.create_ata_ix(wallet, eore_api::consts::MINT_ADDRESS);
+                ixs.push(ix);
+                ata
+            }
             Some(to) => {
                 let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
                 let beneficiary_tokens = spl_associated_token_account::get_associated_token_address(
@@ -114,19 +133,26 @@ impl Miner {
         };
 
         // Build claim instruction with amount or max rewards
+        let amount = match (claim_args.amount, stake) {
+            (Some(amount), _) => crate::utils::amount_f64_to_u64(amount),
+            (None, Some(stake)) => stake.rewards,
+            (None, None) => return Err(required_offline("the amount to claim")),
+        };
         ixs.push(eore_boost_api::sdk::claim(
             pubkey,
             beneficiary,
             mint_address,
-            claim_args
-                .amount
-                .map(|a| crate::utils::amount_f64_to_u64(a))
-                .unwrap_or(stake.rewards),
+            amount,
         ));
 
         // Send and confirm transaction
+        if claim_args.offline.is_offline() {
+            return self
+                .export_transaction(&ixs, ComputeBudget::Dynamic, &claim_args.offline)
+                .await;
+        }
         println!("Claiming staking yield...");
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(100_000), false)
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
             .await
             .ok();
 
@@ -429,53 +455,62 @@ impl Miner {
             }
         };
 
-        // Get token account and mint info
-        println!("Fetching mint account data...");
-        let mint_data = match self.rpc_client.get_account_data(&mint_address).await {
-            Ok(data) => data,
-            Err(err) => {
-                println!("ERROR: Failed to fetch mint account data: {}", err);
-                return Err(Error::Internal("Failed to fetch mint account".to_string()));
-            }
-        };
-        
-        let mint = match Mint::unpack(&mint_data) {
-            Ok(mint) => {
-                println!("Successfully unpacked mint data. Decimals: {}", mint.decimals);
-                mint
-            }
-            Err(err) => {
-                println!("ERROR: Failed to unpack mint data: {}", err);
-                return Err(Error::Internal("Failed to parse mint account".to_string()));
-            }
-        };
+        // Get token account and mint info, unless building offline
+        let (decimals, balance) = if args.offline.is_air_gapped() {
+            (offline_mint_decimals(mint_address, &args.offline)?, None)
+        } else {
+            println!("Fetching mint account data...");
+            let mint_data = match self.rpc_client.get_account_data(&mint_address).await {
+                Ok(data) => data,
+                Err(err) => {
+                    println!("ERROR: Failed to fetch mint account data: {}", err);
+                    return Err(Error::Internal("Failed to fetch mint account".to_string()));
+                }
+            };
 
-        println!("Fetching token account...");
-        let token_account = match self.rpc_client.get_token_account(&sender).await {
-            Ok(Some(account)) => {
-                println!("Found token account with balance: {}", account.token_amount.amount);
-                account
-            }
-            Ok(None) => {
-                println!("ERROR: Token account not found");
-                return Err(Error::Internal("Token account not found".to_string()));
-            }
-            Err(err) => {
-                println!("ERROR: Failed to fetch token account: {}", err);
-                return Err(Error::Internal("Failed to fetch token account".to_string()));
-            }
+            let mint = match Mint::unpack(&mint_data) {
+                Ok(mint) => {
+                    println!("Successfully unpacked mint data. Decimals: {}", mint.decimals);
+                    mint
+                }
+                Err(err) => {
+                    println!("ERROR: Failed to unpack mint data: {}", err);
+                    return Err(Error::Internal("Failed to parse mint account".to_string()));
+                }
+            };
+
+            println!("Fetching token account...");
+            let token_account = match self.rpc_client.get_token_account(&sender).await {
+                Ok(Some(account)) => {
+                    println!("Found token account with balance: {}", account.token_amount.amount);
+                    account
+                }
+                Ok(None) => {
+                    println!("ERROR: Token account not found");
+                    return Err(Error::Internal("Token account not found".to_string()));
+                }
+                Err(err) => {
+                    println!("ERROR: Failed to fetch token account: {}", err);
+                    return Err(Error::Internal("Failed to fetch token account".to_string()));
+                }
+            };
+            (mint.decimals, Some(token_account.token_amount.amount))
         };
 
         // Parse amount
-        let amount: u64 = if let Some(amount) = args.amount {
-            let calculated = (amount * 10f64.powf(mint.decimals as f64)) as u64;
-            println!("Using provided amount: {} (raw: {})", amount, calculated);
-            calculated
-        } else {
-            let balance = u64::from_str(token_account.token_amount.amount.as_str())
-                .expect("Failed to parse token balance");
-            println!("Using full balance amount: {}", balance);
-            balance
+        let amount: u64 = match (args.amount, balance) {
+            (Some(amount), _) => {
+                let calculated = (amount * 10f64.powf(decimals as f64)) as u64;
+                println!("Using provided amount: {} (raw: {})", amount, calculated);
+                calculated
+            }
+            (None, Some(balance)) => {
+                let balance = u64::from_str(balance.as_str())
+                    .expect("Failed to parse token balance");
+                println!("Using full balance amount: {}", balance);
+                balance
+            }
+            (None, None) => return Err(required_offline("the amount to deposit")),
         };
 
         // Get addresses
@@ -486,29 +521,41 @@ impl Miner {
         println!("Derived stake PDA: {}", stake_address);
 
         // Check if boost account exists
-        println!("Checking boost account...");
-        let boost = match get_boost(&self.rpc_client, boost_address).await {
-            Ok(boost) => {
-                println!("Found boost account with weight: {}", boost.weight);
-                boost
-            }
-            Err(err) => {
-                println!("ERROR: No boost account found for mint {}", mint_address);
-                println!("Error details: {}", err);
-                return Err(Error::Internal("Boost account not found".to_string()));
+        if !args.offline.is_air_gapped() {
+            println!("Checking boost account...");
+            match get_boost(&self.rpc_client, boost_address).await {
+                Ok(boost) => {
+                    println!("Found boost account with weight: {}", boost.weight);
+                }
+                Err(err) => {
+                    println!("ERROR: No boost account found for mint {}", mint_address);
+                    println!("Error details: {}", err);
+                    return Err(Error::Internal("Boost account not found".to_string()));
+                }
             }
-        };
+        }
 
         // Check and initialize stake account if needed
         println!("Checking if stake account needs initialization...");
-        if self.rpc_client.get_account_data(&stake_address).await.is_err() {
+        let mut ixs = vec![];
+        let open_stake = if args.offline.is_air_gapped() {
+            args.open_stake
+        } else {
+            self.rpc_client.get_account_data(&stake_address).await.is_err()
+        };
+        if open_stake {
             println!("Stake account not found, initializing...");
             let ix = eore_boost_api::sdk::open(signer.pubkey(), signer.pubkey(), mint_address);
-            match self.send_and_confirm(&[ix], ComputeBudget::Fixed(50_000), false).await {
-                Ok(_) => println!("Successfully initialized stake account"),
-                Err(err) => {
-                    println!("ERROR: Failed to initialize stake account: {}", err);
-                    return Err(Error::Internal("Failed to initialize stake account".to_string()));
+            if args.offline.is_offline() {
+                // Open the account in the same transaction
+                ixs.push(ix);
+            } else {
+                match self.send_and_confirm(&[ix], ComputeBudget::Dynamic, false).await {
+                    Ok(_) => println!("Successfully initialized stake account"),
+                    Err(err) => {
+                        println!("ERROR: Failed to initialize stake account: {}", err);
+                        return Err(Error::Internal("Failed to initialize stake account".to_string()));
+                    }
                 }
             }
         } else {
@@ -516,9 +563,15 @@ impl Miner {
         }
 
         // Send deposit transaction
-        println!("Sending deposit transaction...");
         let ix = eore_boost_api::sdk::deposit(signer.pubkey(), mint_address, amount);
-        match self.send_and_confirm(&[ix], ComputeBudget::Fixed(50_000), false).await {
+        ixs.push(ix);
+        if args.offline.is_offline() {
+            return self
+                .export_transaction(&ixs, ComputeBudget::Dynamic, &args.offline)
+                .await;
+        }
+        println!("Sending deposit transaction...");
+        match self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false).await {
             Ok(_) => {
                 println!("Successfully deposited {} tokens", amount);
                 Ok(())
@@ -557,7 +610,11 @@ impl Miner {
 
         // Create token account if necessary
         let mut ixs = vec![];
-        if self
+        if args.offline.is_offline() {
+            if args.token_account.is_none() {
+                ixs.push(self.create_ata_ix(signer.pubkey(), mint_address).1);
+            }
+        } else if self
             .rpc_client
             .get_token_account(&beneficiary)
             .await
@@ -573,45 +630,50 @@ impl Miner {
             );
         };
 
-        // Get mint account
-        let mint_data = self
-            .rpc_client
-            .get_account_data(&mint_address)
-            .await
-            .expect("Failed to fetch mint account");
-        let mint = Mint::unpack(&mint_data).expect("Failed to parse mint account");
-
         // Get addresses
         let boost_address = boost_pda(mint_address).0;
         let stake_address = stake_pda(signer.pubkey(), boost_address).0;
-        
-        // Check if boost account exists
-        let _boost = match get_boost(&self.rpc_client, boost_address).await {
-            Ok(boost) => boost,
-            Err(err) => {
-                println!("{}: No boost account found for this mint.", "ERROR".bold().red());
-                println!("Make sure you are using a valid mint address that has an associated boost account.");
-                println!("Error details: {}", err);
-                return Err(Error::Internal("Boost account not found".to_string()));
-            }
-        };
-        
-        // Check if stake account exists
-        let stake = match get_stake(&self.rpc_client, stake_address).await {
-            Ok(stake) => stake,
-            Err(err) => {
-                println!("{}: No stake account found for this mint.", "ERROR".bold().red());
-                println!("You may need to deposit funds first before withdrawing.");
-                println!("Error details: {}", err);
-                return Err(Error::Internal("Stake account not found".to_string()));
-            }
+
+        // Get mint, boost and stake accounts, unless building offline
+        let (decimals, stake) = if args.offline.is_air_gapped() {
+            (offline_mint_decimals(mint_address, &args.offline)?, None)
+        } else {
+            let mint_data = self
+                .rpc_client
+                .get_account_data(&mint_address)
+                .await
+                .expect("Failed to fetch mint account");
+            let mint = Mint::unpack(&mint_data).expect("Failed to parse mint account");
+
+            // Check if boost account exists
+            let _boost = match get_boost(&self.rpc_client, boost_address).await {
+                Ok(boost) => boost,
+                Err(err) => {
+                    println!("{}: No boost account found for this mint.", "ERROR".bold().red());
+                    println!("Make sure you are using a valid mint address that has an associated boost account.");
+                    println!("Error details: {}", err);
+                    return Err(Error::Internal("Boost account not found".to_string()));
+                }
+            };
+
+            // Check if stake account exists
+            let stake = match get_stake(&self.rpc_client, stake_address).await {
+                Ok(stake) => stake,
+                Err(err) => {
+                    println!("{}: No stake account found for this mint.", "ERROR".bold().red());
+                    println!("You may need to deposit funds first before withdrawing.");
+                    println!("Error details: {}", err);
+                    return Err(Error::Internal("Stake account not found".to_string()));
+                }
+            };
+            (mint.decimals, Some(stake))
         };
 
         // Parse amount
-        let amount: u64 = if let Some(amount) = args.amount {
-            (amount * 10f64.powf(mint.decimals as f64)) as u64
-        } else {
-            stake.balance
+        let amount: u64 = match (args.amount, stake) {
+            (Some(amount), _) => (amount * 10f64.powf(decimals as f64)) as u64,
+            (None, Some(stake)) => stake.balance,
+            (None, None) => return Err(required_offline("the amount to withdraw")),
         };
 
         // Send tx
@@ -620,7 +682,12 @@ impl Miner {
             mint_address,
             amount,
         ));
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(200_000), false)
+        if args.offline.is_offline() {
+            return self
+                .export_transaction(&ixs, ComputeBudget::Dynamic, &args.offline)
+                .await;
+        }
+        self.send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
             .await
             .ok();
 
@@ -698,6 +765,18 @@ impl Miner {
     }
 }
 
+/// Decimals of a mint whose account can't be fetched offline, from
+/// `--mint-decimals` or, for BITZ, its known decimals.
+fn offline_mint_decimals(mint_address: Pubkey, offline: &OfflineArgs) -> Result<u8, Error> {
+    match offline.mint_decimals {
+        Some(decimals) => Ok(decimals),
+        None if mint_address == eore_api::consts::MINT_ADDRESS => {
+            Ok(eore_api::consts::TOKEN_DECIMALS)
+        }
+        None => Err(required_offline("--mint-decimals")),
+    }
+}
+
 pub fn calculate_claimable_yield(
     boost: Boost,
     boost_config: BoostConfig,
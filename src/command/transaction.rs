@@ -7,15 +7,34 @@ use solana_sdk::signature::Signature;
 use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding};
 use tabled::{settings::{object::{Columns, Rows}, Alignment, Remove, Style}, Table};
 
-use crate::{error::Error, utils::{amount_u64_to_string, format_timestamp, TableData, TableSectionTitle}, Miner, TransactionArgs};
+use crate::{error::Error, utils::{amount_u64_to_string, decode_transaction_error, format_timestamp, TableData, TableSectionTitle}, Miner, TransactionArgs};
 
 impl Miner {
     pub async fn transaction(&self, args: TransactionArgs) -> Result<(), Error> {
         let signature = args.signature;
         let signature = Signature::from_str(&signature).expect("Failed to parse signature");
-        match self.rpc_client.get_transaction(&signature, UiTransactionEncoding::Json).await {
+        match self.rpc_client.get_transaction(&signature, UiTransactionEncoding::Base64).await {
             Ok(tx) => {
                 let mut data = vec![];
+
+                // Decode the error of a failed transaction against the programs it called
+                let failure = tx.transaction.meta.as_ref().and_then(|meta| meta.err.clone()).map(|err| {
+                    let program_ids = tx
+                        .transaction
+                        .transaction
+                        .decode()
+                        .map(|decoded| {
+                            let keys = decoded.message.static_account_keys();
+                            decoded
+                                .message
+                                .instructions()
+                                .iter()
+                                .map(|ix| keys[ix.program_id_index as usize])
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    decode_transaction_error(&err, &program_ids)
+                });
                 
                 // Parse transaction response
                 if let Some(meta) = tx.transaction.meta {
@@ -70,6 +89,41 @@ impl Miner {
                     }
                 }
 
+                // Show failed transactions without a mine event
+                if let (true, Some(err)) = (data.is_empty(), failure) {
+                    data.push(TableData {
+                        key: "Signature".to_string(),
+                        value: signature.to_string(),
+                    });
+                    data.push(TableData {
+                        key: "Block".to_string(),
+                        value: tx.slot.to_string(),
+                    });
+                    data.push(TableData {
+                        key: "Timestamp".to_string(),
+                        value: format_timestamp(tx.block_time.unwrap_or_default()),
+                    });
+                    data.push(TableData {
+                        key: "Status".to_string(),
+                        value: "Failed".bold().red().to_string(),
+                    });
+                    data.push(TableData {
+                        key: "Error".to_string(),
+                        value: match err.instruction {
+                            Some(index) => format!("{} {} (instruction #{})", err.program, err.name, index),
+                            None => err.name.clone(),
+                        },
+                    });
+                    data.push(TableData {
+                        key: "Explanation".to_string(),
+                        value: err.explanation.clone(),
+                    });
+                    data.push(TableData {
+                        key: "Retryable".to_string(),
+                        value: if err.is_retryable() { "Yes" } else { "No" }.to_string(),
+                    });
+                }
+
                 // Check if data is empty
                 if data.is_empty() {
                     return Err(Error::Internal("Unknown transaction".to_string())).map_err(From::from);
@@ -9,12 +9,14 @@ use spl_token::amount_to_ui_amount;
 
 use crate::{
     args::TransferArgs,
+    error::Error,
+    send::RetryPolicy,
     utils::{amount_f64_to_u64, ask_confirm, ComputeBudget},
     Miner,
 };
 
 impl Miner {
-    pub async fn transfer(&self, args: TransferArgs) {
+    pub async fn transfer(&self, args: TransferArgs) -> Result<(), Error> {
         let signer = self.signer();
         let pubkey = signer.pubkey();
         let sender_tokens =
@@ -25,7 +27,9 @@ impl Miner {
         let to = Pubkey::from_str(&args.to).expect("Failed to parse recipient wallet address");
         let recipient_tokens =
             spl_associated_token_account::get_associated_token_address(&to, &MINT_ADDRESS);
-        if self
+        if args.offline.is_offline() {
+            ixs.push(self.create_ata_ix(to, MINT_ADDRESS).1);
+        } else if self
             .rpc_client
             .get_token_account(&recipient_tokens)
             .await
@@ -56,7 +60,7 @@ impl Miner {
             )
             .as_str(),
         ) {
-            return;
+            return Ok(());
         }
 
         // Send and confirm
@@ -71,8 +75,18 @@ impl Miner {
             )
             .unwrap(),
         );
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(32_000), false)
-            .await
-            .ok();
+        if args.offline.is_offline() {
+            return self
+                .export_transaction(&ixs, ComputeBudget::Fixed(32_000), &args.offline)
+                .await;
+        }
+        self.send_and_confirm_with_policy(
+            &ixs,
+            ComputeBudget::Fixed(32_000),
+            false,
+            RetryPolicy::quick(),
+        )
+        .await?;
+        Ok(())
     }
 }
@@ -0,0 +1,82 @@
+use colored::*;
+use solana_sdk::signature::Signature;
+
+use crate::{
+    args::{TxArgs, TxCommand, TxSignArgs, TxSubmitArgs},
+    error::Error,
+    send::{required_signers, sign_with, OfflineTransaction},
+    Miner,
+};
+
+impl Miner {
+    pub async fn tx(&self, args: TxArgs) -> Result<(), Error> {
+        match args.command {
+            TxCommand::Sign(args) => self.tx_sign(args),
+            TxCommand::Submit(args) => self.tx_submit(args).await,
+        }
+    }
+
+    fn tx_sign(&self, args: TxSignArgs) -> Result<(), Error> {
+        // Sign with the local keypairs, keeping the signatures already present
+        let mut tx = OfflineTransaction::read(&args.file)?.transaction()?;
+        let hash = tx.message.recent_blockhash;
+        sign_with(&mut tx, &[&self.signer(), &self.fee_payer()], hash)?;
+
+        // Write to file
+        let output = args.output.unwrap_or(args.file);
+        let offline_tx = OfflineTransaction::new(&tx)?;
+        offline_tx.write(&output)?;
+        offline_tx.print_signers();
+        println!("Wrote transaction to {}", output);
+        println!("Submit it with `bitz tx submit {}`", output);
+        Ok(())
+    }
+
+    async fn tx_submit(&self, args: TxSubmitArgs) -> Result<(), Error> {
+        // Combine the signatures of every file into the first transaction
+        let mut tx = OfflineTransaction::read(&args.files[0])?.transaction()?;
+        for file in args.files.iter().skip(1) {
+            let other = OfflineTransaction::read(file)?.transaction()?;
+            if other.message != tx.message {
+                return Err(Error::Internal(format!(
+                    "{} holds a different transaction than {}",
+                    file, args.files[0]
+                )));
+            }
+            for (i, signature) in other.signatures.into_iter().enumerate() {
+                if signature != Signature::default() {
+                    tx.signatures[i] = signature;
+                }
+            }
+        }
+
+        // Add missing signatures of the local keypairs
+        if !args.no_sign && !tx.is_signed() {
+            let hash = tx.message.recent_blockhash;
+            sign_with(&mut tx, &[&self.signer(), &self.fee_payer()], hash)?;
+        }
+
+        // Check every signer has signed, and with a valid signature
+        let message = tx.message_data();
+        let mut missing = vec![];
+        for (pubkey, signature) in required_signers(&tx) {
+            if signature == Signature::default() {
+                missing.push(pubkey);
+            } else if !signature.verify(pubkey.as_ref(), &message) {
+                return Err(Error::Internal(format!("invalid signature for {}", pubkey)));
+            }
+        }
+        if !missing.is_empty() {
+            for pubkey in missing.iter() {
+                println!("{} Missing signature for {}", "ERROR".bold().red(), pubkey);
+            }
+            return Err(Error::Internal(
+                "transaction is not fully signed".to_string(),
+            ));
+        }
+
+        // Send and confirm
+        self.send_and_confirm_signed(&tx, false).await?;
+        Ok(())
+    }
+}
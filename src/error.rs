@@ -6,12 +6,87 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
     #[error("solana parse pubkey")]
     SolanaParsePubkey(#[from] solana_sdk::pubkey::ParsePubkeyError),
-    #[error("solana rpc client")]
-    SolanaRpcClient(#[from] solana_client::client_error::ClientError),
+    #[error("solana rpc client: {0}")]
+    SolanaRpcClient(Box<solana_client::client_error::ClientError>),
     #[error("solana program")]
     SolanaProgram(#[from] solana_program::program_error::ProgramError),
+    #[error("signer: {0}")]
+    Signer(#[from] solana_sdk::signer::SignerError),
     #[error("parse int")]
     ParseInt(#[from] std::num::ParseIntError),
     #[error("number of devices per keypair exceeded")]
     TooManyDevices,
+    #[error("pool server reports address {1}, expected {0}")]
+    PoolAddressMismatch(solana_sdk::pubkey::Pubkey, solana_sdk::pubkey::Pubkey),
+    #[error("pool: {0}")]
+    Pool(#[from] PoolError),
+    #[error("pool challenge failed verification: {0}")]
+    PoolChallengeMismatch(String),
+    #[error("{0}")]
+    Program(crate::utils::DecodedError),
+    #[error("fee payer {0} has insufficient balance: {1} ETH")]
+    InsufficientBalance(solana_sdk::pubkey::Pubkey, f64),
+    #[error("transaction {0} not confirmed after {1} attempts")]
+    TransactionTimeout(solana_sdk::signature::Signature, usize),
+}
+
+// Boxed so that `Result<_, Error>` stays small on the happy path
+impl From<solana_client::client_error::ClientError> for Error {
+    fn from(err: solana_client::client_error::ClientError) -> Self {
+        Error::SolanaRpcClient(Box::new(err))
+    }
+}
+
+/// Failures talking to a pool server, classified so callers can tell a
+/// missing registration apart from an unreachable or misbehaving server.
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("pool server unreachable: {0}")]
+    Unreachable(String),
+    #[error("pool server timed out")]
+    Timeout,
+    #[error("member is not registered with the pool")]
+    MemberNotFound,
+    #[error("not found")]
+    NotFound,
+    #[error("request rejected ({0})")]
+    Rejected(reqwest::StatusCode),
+    #[error("pool server error ({0})")]
+    Server(reqwest::StatusCode),
+    #[error("unexpected response: {0}")]
+    Decode(String),
+}
+
+impl From<reqwest::Error> for PoolError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            PoolError::Timeout
+        } else if err.is_decode() {
+            PoolError::Decode(err.to_string())
+        } else if let Some(status) = err.status() {
+            PoolError::from_status(status)
+        } else {
+            PoolError::Unreachable(err.to_string())
+        }
+    }
+}
+
+impl PoolError {
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        if status == reqwest::StatusCode::NOT_FOUND {
+            PoolError::NotFound
+        } else if status.is_server_error() {
+            PoolError::Server(status)
+        } else {
+            PoolError::Rejected(status)
+        }
+    }
+
+    /// Whether retrying the same request later may succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            PoolError::Unreachable(_) | PoolError::Timeout | PoolError::Server(_)
+        )
+    }
 }
@@ -2,22 +2,26 @@ mod args;
 mod command;
 mod error;
 mod send;
+mod signer;
 mod utils;
 
 use colored::*;
 use crossterm::style::Stylize;
 use futures::StreamExt;
-use std::{sync::Arc, sync::RwLock};
+use std::{sync::Arc, sync::Mutex, sync::RwLock};
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
 use args::*;
-use clap::{command, Parser, Subcommand};
+use send::{DynamicFee, FeeController, FeePayerPool, FeeStrategy};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
-    signature::{read_keypair_file, Keypair, Signer},
+    pubkey::Pubkey,
+    signature::Signer,
 };
+use signer::DynSigner;
 use utils::{PoolCollectingData, SoloCollectingData, Tip};
 
 // TODO: Unify balance and proof into "account"
@@ -27,12 +31,20 @@ use utils::{PoolCollectingData, SoloCollectingData, Tip};
 
 #[derive(Clone)]
 struct Miner {
-    pub keypair_filepath: Option<String>,
+    pub signer: DynSigner,
     pub priority_fee: Option<u64>,
-    pub dynamic_fee_url: Option<String>,
-    pub dynamic_fee: bool,
+    pub fee_estimator: Option<Arc<DynamicFee>>,
     pub rpc_client: Arc<RpcClient>,
-    pub fee_payer_filepath: Option<String>,
+    pub fee_payers: Arc<FeePayerPool>,
+    pub pool_auth_filepath: Option<String>,
+    pub proxy: Option<String>,
+    pub send_rpc_clients: Vec<Arc<RpcClient>>,
+    pub fee_controller: Option<Arc<Mutex<FeeController>>>,
+    pub send_attempts: Option<usize>,
+    pub mining_send_attempts: Option<usize>,
+    pub confirm_timeout: Option<u64>,
+    pub ws_url: Option<String>,
+    pub lookup_tables: Vec<Pubkey>,
     pub solo_collecting_data: Arc<std::sync::RwLock<Vec<SoloCollectingData>>>,
     pub pool_collecting_data: Arc<std::sync::RwLock<Vec<PoolCollectingData>>>,
 }
@@ -48,6 +60,9 @@ enum Commands {
     #[command(about = "Claim your collecting yield")]
     Claim(ClaimArgs),
 
+    #[command(about = "Manage option defaults in the bitz config file")]
+    Config(ConfigArgs),
+
     #[cfg(feature = "admin")]
     #[command(about = "Initialize the program")]
     Initialize(InitializeArgs),
@@ -55,6 +70,15 @@ enum Commands {
     #[command(about = "Start collecting on your local machine")]
     Collect(CollectArgs),
 
+    #[command(about = "Generate, import, export, encrypt and serve keypairs")]
+    Key(KeyArgs),
+
+    #[command(about = "Manage address lookup tables for versioned transactions")]
+    LookupTable(LookupTableArgs),
+
+    #[command(about = "Manage durable nonce accounts for offline signing")]
+    Nonce(NonceArgs),
+
     #[command(about = "Connect to a collecting pool")]
     Pool(PoolArgs),
 
@@ -69,6 +93,9 @@ enum Commands {
 
     #[command(about = "Send BITZ to another user")]
     Transfer(TransferArgs),
+
+    #[command(about = "Submit transactions signed offline")]
+    Tx(TxArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -94,8 +121,16 @@ struct Args {
 
     #[arg(
         long,
-        value_name = "KEYPAIR_FILEPATH",
-        help = "Filepath to signer keypair. Base58 or Raw JSON.",
+        value_name = "PROFILE",
+        help = "Named profile of the bitz config file to take option defaults from.",
+        global = true
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KEYPAIR",
+        help = "Signer keypair: a file holding a base58 or JSON secret key or an encrypted keystore, env:VAR, prompt:, the url of a remote signer, or pubkey:ADDRESS to build transactions that are signed elsewhere.",
         default_value = "key.txt",
         global = true
     )]
@@ -103,11 +138,29 @@ struct Args {
 
     #[arg(
         long,
-        value_name = "FEE_PAYER_FILEPATH",
-        help = "Filepath to transaction fee payer keypair.",
+        value_name = "FEE_PAYER",
+        help = "Transaction fee payer keypair, in any form --keypair takes. May be repeated to rotate between fee payers as they run low on ETH.",
         global = true
     )]
-    fee_payer: Option<String>,
+    fee_payer: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "KEYPAIR",
+        help = "Keypair, in any form --keypair takes, of a wallet that tops up fee payers when they all run low on ETH.",
+        global = true
+    )]
+    funding_wallet: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ETH",
+        help = "Amount of ETH to top up a fee payer with from the funding wallet.",
+        default_value = "0.01",
+        requires = "funding_wallet",
+        global = true
+    )]
+    top_up_amount: f64,
 
     #[arg(
         long,
@@ -129,6 +182,138 @@ struct Args {
     #[arg(long, help = "Enable dynamic priority fees", global = true)]
     dynamic_fee: bool,
 
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "Source of dynamic fee estimates. Auto picks a provider from the dynamic fee url.",
+        default_value = "auto",
+        global = true
+    )]
+    fee_strategy: FeeStrategy,
+
+    #[arg(
+        long,
+        value_name = "PERCENTILE",
+        help = "Percentile of recent fees to pay, for estimators that return fee samples.",
+        default_value = "75",
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        global = true
+    )]
+    fee_percentile: u8,
+
+    #[arg(
+        long,
+        value_name = "WEIGHT",
+        help = "Weight of the newest fee estimate, between 0 and 1. Lower values smooth fees over time.",
+        default_value = "1.0",
+        global = true
+    )]
+    fee_smoothing: f64,
+
+    #[arg(
+        long,
+        value_name = "METHOD",
+        help = "JSON-RPC method to call on the dynamic fee url with the custom fee strategy.",
+        global = true
+    )]
+    fee_rpc_method: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "POOL_AUTH_FILEPATH",
+        help = "Filepath to a JSON file of per-pool auth headers and client certificates.",
+        global = true
+    )]
+    pool_auth: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PROXY_URL",
+        help = "Proxy for all RPC and pool traffic (http, https or socks5). Defaults to HTTPS_PROXY or ALL_PROXY.",
+        global = true
+    )]
+    proxy: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "Additional send-only RPC endpoint to broadcast transactions to. May be repeated.",
+        global = true
+    )]
+    send_rpc: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Adapt the priority fee of mining transactions to their landing rate, capped by --priority-fee",
+        global = true
+    )]
+    adaptive_fee: bool,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Lowest priority fee the adaptive fee controller may use.",
+        default_value = "0",
+        global = true
+    )]
+    fee_floor: u64,
+
+    #[arg(
+        long,
+        value_name = "RATE",
+        help = "Share of mining transactions the adaptive fee controller aims to land, between 0 and 1.",
+        default_value = "0.9",
+        global = true
+    )]
+    target_landing_rate: f64,
+
+    #[arg(
+        long,
+        value_name = "ATTEMPTS",
+        help = "Override how many times a transaction is sent before giving up. 0 retries forever. Mining transactions are not affected.",
+        global = true
+    )]
+    send_attempts: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "ATTEMPTS",
+        help = "Override how many times a mining transaction is sent before giving up. By default they are retried forever.",
+        global = true
+    )]
+    mining_send_attempts: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Override how long to wait for confirmation after each send.",
+        global = true
+    )]
+    confirm_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "WEBSOCKET_URL",
+        help = "PubSub endpoint for transaction confirmations. Defaults to the RPC url's websocket endpoint.",
+        global = true
+    )]
+    ws_url: Option<String>,
+
+    #[arg(
+        long,
+        help = "Poll for transaction confirmations instead of subscribing over websocket.",
+        global = true
+    )]
+    no_ws: bool,
+
+    #[arg(
+        long,
+        value_name = "LOOKUP_TABLE_ADDRESS",
+        help = "Address lookup table to send versioned transactions with. May be repeated.",
+        global = true
+    )]
+    lookup_table: Vec<Pubkey>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -138,7 +323,33 @@ async fn main() {
     // Initialize logger
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
 
-    let args = Args::parse();
+    // Take option defaults from the environment and the bitz config file
+    let profile = utils::selected_profile();
+    let values = utils::BitzConfig::load()
+        .and_then(|config| config.resolve(profile.as_deref()))
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        });
+    let matches = utils::apply_config(Args::command(), &values).get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    // Edit the config without setting up a miner
+    if let Commands::Config(config_args) = args.command {
+        if let Err(err) = Miner::config(config_args, profile.as_deref(), &Args::command()) {
+            println!("{:?}", err);
+        }
+        return;
+    }
+
+    // Manage keypairs without setting up a miner
+    if let Commands::Key(key_args) = args.command {
+        let proxy = utils::resolve_proxy(args.proxy);
+        if let Err(err) = Miner::key(key_args, args.keypair, proxy).await {
+            println!("{:?}", err);
+        }
+        return;
+    }
 
     // Load the config file from custom path, the default path, or use default config values
     let cli_config = if let Some(config_file) = &args.config_file {
@@ -164,19 +375,112 @@ async fn main() {
     // Initialize miner.
     let cluster = args.rpc.unwrap_or(cli_config.json_rpc_url);
     let default_keypair = args.keypair.unwrap_or(cli_config.keypair_path.clone());
-    let fee_payer_filepath = args.fee_payer.unwrap_or(default_keypair.clone());
-    let rpc_client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
+    let fee_payer_sources = if args.fee_payer.is_empty() {
+        vec![default_keypair.clone()]
+    } else {
+        args.fee_payer
+    };
+
+    // Load the keypairs once, up front
+    let proxy = utils::resolve_proxy(args.proxy);
+    let load_signer = |source: &str| {
+        signer::load_signer(source, proxy.as_deref()).unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        })
+    };
+    let signer = load_signer(&default_keypair);
+    let fee_payers = Arc::new(FeePayerPool::new(
+        fee_payer_sources.iter().map(|source| load_signer(source)).collect(),
+        args.funding_wallet.as_deref().map(load_signer),
+        args.top_up_amount,
+    ));
+
+    // Websockets can't go through the proxy, so only use one if given explicitly
+    let ws_url = if args.no_ws {
+        None
+    } else if args.ws_url.is_some() {
+        args.ws_url
+    } else if proxy.is_none() {
+        utils::websocket_url(&cluster)
+    } else {
+        None
+    };
+    let rpc_client =
+        utils::new_rpc_client(cluster, CommitmentConfig::confirmed(), proxy.as_deref())
+            .unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            });
+
+    let send_rpc_clients = args
+        .send_rpc
+        .into_iter()
+        .map(|url| {
+            utils::new_rpc_client(url, CommitmentConfig::confirmed(), proxy.as_deref())
+                .map(Arc::new)
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                })
+        })
+        .collect();
+
+    let rpc_client = Arc::new(rpc_client);
+    let fee_estimator = if args.dynamic_fee {
+        let http_client = utils::http_client_builder(proxy.as_deref())
+            .and_then(|builder| builder.build().map_err(From::from))
+            .unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            });
+        let fee_estimator = DynamicFee::new(
+            args.fee_strategy,
+            args.dynamic_fee_url.unwrap_or(rpc_client.url()),
+            args.fee_rpc_method,
+            args.fee_percentile,
+            args.fee_smoothing,
+            rpc_client.clone(),
+            http_client,
+        )
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        });
+        Some(Arc::new(fee_estimator))
+    } else {
+        if args.fee_strategy != FeeStrategy::Auto || args.fee_rpc_method.is_some() {
+            eprintln!("warning: fee estimation options have no effect without --dynamic-fee");
+        }
+        None
+    };
+
+    let fee_controller = args.adaptive_fee.then(|| {
+        Arc::new(Mutex::new(FeeController::new(
+            args.fee_floor,
+            args.priority_fee.unwrap_or(args.fee_floor),
+            args.target_landing_rate,
+        )))
+    });
 
     let solo_collecting_data = Arc::new(RwLock::new(Vec::new()));
     let pool_collecting_data = Arc::new(RwLock::new(Vec::new()));
 
     let miner = Arc::new(Miner::new(
-        Arc::new(rpc_client),
+        rpc_client,
         args.priority_fee,
-        Some(default_keypair),
-        args.dynamic_fee_url,
-        args.dynamic_fee,
-        Some(fee_payer_filepath),
+        signer,
+        fee_estimator,
+        fee_payers,
+        args.pool_auth,
+        proxy,
+        send_rpc_clients,
+        fee_controller,
+        args.send_attempts,
+        args.mining_send_attempts,
+        args.confirm_timeout,
+        ws_url,
+        args.lookup_table,
         solo_collecting_data,
         pool_collecting_data,
     ));
@@ -197,6 +501,18 @@ async fn main() {
                 println!("{:?}", err);
             }
         }
+        Commands::Config(_) => unreachable!(),
+        Commands::Key(_) => unreachable!(),
+        Commands::LookupTable(args) => {
+            if let Err(err) = miner.lookup_table(args).await {
+                println!("{:?}", err);
+            }
+        }
+        Commands::Nonce(args) => {
+            if let Err(err) = miner.nonce(args).await {
+                println!("{:?}", err);
+            }
+        }
         Commands::Pool(args) => {
             miner.pool(args).await;
         }
@@ -212,11 +528,18 @@ async fn main() {
             miner.stake(args).await;
         }
         Commands::Transfer(args) => {
-            miner.transfer(args).await;
+            if let Err(err) = miner.transfer(args).await {
+                println!("{:?}", err);
+            }
         }
         Commands::Transaction(args) => {
             miner.transaction(args).await.unwrap();
         }
+        Commands::Tx(args) => {
+            if let Err(err) = miner.tx(args).await {
+                println!("{:?}", err);
+            }
+        }
         #[cfg(feature = "admin")]
         Commands::Initialize(_) => {
             miner.initialize().await;
@@ -228,59 +551,46 @@ impl Miner {
     pub fn new(
         rpc_client: Arc<RpcClient>,
         priority_fee: Option<u64>,
-        keypair_filepath: Option<String>,
-        dynamic_fee_url: Option<String>,
-        dynamic_fee: bool,
-        fee_payer_filepath: Option<String>,
+        signer: DynSigner,
+        fee_estimator: Option<Arc<DynamicFee>>,
+        fee_payers: Arc<FeePayerPool>,
+        pool_auth_filepath: Option<String>,
+        proxy: Option<String>,
+        send_rpc_clients: Vec<Arc<RpcClient>>,
+        fee_controller: Option<Arc<Mutex<FeeController>>>,
+        send_attempts: Option<usize>,
+        mining_send_attempts: Option<usize>,
+        confirm_timeout: Option<u64>,
+        ws_url: Option<String>,
+        lookup_tables: Vec<Pubkey>,
         solo_collecting_data: Arc<std::sync::RwLock<Vec<SoloCollectingData>>>,
         pool_collecting_data: Arc<std::sync::RwLock<Vec<PoolCollectingData>>>,
     ) -> Self {
         Self {
             rpc_client,
-            keypair_filepath,
+            signer,
             priority_fee,
-            dynamic_fee_url,
-            dynamic_fee,
-            fee_payer_filepath,
+            fee_estimator,
+            fee_payers,
+            pool_auth_filepath,
+            proxy,
+            send_rpc_clients,
+            fee_controller,
+            send_attempts,
+            mining_send_attempts,
+            confirm_timeout,
+            ws_url,
+            lookup_tables,
             solo_collecting_data,
             pool_collecting_data,
         }
     }
 
-    pub fn signer(&self) -> Keypair {
-        match self.keypair_filepath.clone() {
-            Some(filepath) => Miner::read_keypair_from_file(filepath.clone()),
-            None => panic!("No keypair provided"),
-        }
-    }
-
-    pub fn read_keypair_from_file(filepath: String) -> Keypair {
-        // 首先判断文件是否存在
-        if !std::path::Path::new(&filepath).exists() {
-            panic!("File not found at {}", filepath);
-        }
-
-        // 先尝试 read_keypair_file
-        match read_keypair_file(&filepath) {
-            Ok(keypair) => keypair,
-            Err(_) => {
-                // 如果读取文件失败，尝试读取文件内容作为 base58 字符串
-                match std::fs::read_to_string(&filepath) {
-                    Ok(content) => {
-                        // 移除可能的空白字符
-                        let content = content.trim();
-                        Keypair::from_base58_string(content)
-                    }
-                    Err(_) => panic!("Could not read file content: {}", filepath),
-                }
-            }
-        }
+    pub fn signer(&self) -> DynSigner {
+        self.signer.clone()
     }
 
-    pub fn fee_payer(&self) -> Keypair {
-        match self.fee_payer_filepath.clone() {
-            Some(filepath) => Miner::read_keypair_from_file(filepath.clone()),
-            None => panic!("No fee payer keypair provided"),
-        }
+    pub fn fee_payer(&self) -> DynSigner {
+        self.fee_payers.active()
     }
 }
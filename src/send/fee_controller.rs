@@ -0,0 +1,165 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Number of recent mining transactions the landing rate is measured over.
+const WINDOW_SIZE: usize = 20;
+/// Outcomes required before the controller starts lowering the fee.
+const MIN_SAMPLES: usize = 5;
+/// Landings slower than this do not count towards lowering the fee.
+const SLOW_CONFIRM: Duration = Duration::from_secs(20);
+/// Smallest step the fee moves by, as a percentage of the cap.
+const MIN_STEP_PERCENT: u64 = 1;
+/// Floor on the smallest step, in microlamports.
+const MIN_STEP: u64 = 10;
+
+/// Tunes the priority fee of mining transactions from their own landing
+/// outcomes. Misses raise the fee multiplicatively; sustained fast landings
+/// above the target rate lower it gradually, always within `[floor, cap]`.
+pub struct FeeController {
+    floor: u64,
+    cap: u64,
+    target_landing_rate: f64,
+    fee: u64,
+    outcomes: VecDeque<FeeOutcome>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FeeOutcome {
+    pub fee: u64,
+    pub landed: bool,
+    pub time_to_confirm: Duration,
+}
+
+impl FeeController {
+    pub fn new(floor: u64, cap: u64, target_landing_rate: f64) -> Self {
+        let cap = cap.max(floor);
+        Self {
+            floor,
+            cap,
+            target_landing_rate: target_landing_rate.clamp(0.0, 1.0),
+            fee: floor,
+            outcomes: VecDeque::with_capacity(WINDOW_SIZE),
+        }
+    }
+
+    /// The fee to price the next mining transaction at.
+    pub fn fee(&self) -> u64 {
+        self.fee
+    }
+
+    /// Share of recent mining transactions that landed.
+    pub fn landing_rate(&self) -> Option<f64> {
+        if self.outcomes.is_empty() {
+            return None;
+        }
+        let landed = self.outcomes.iter().filter(|o| o.landed).count();
+        Some(landed as f64 / self.outcomes.len() as f64)
+    }
+
+    /// Records the outcome of a transaction and adjusts the next fee.
+    pub fn record(&mut self, outcome: FeeOutcome) {
+        if self.outcomes.len() == WINDOW_SIZE {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(outcome);
+
+        let rate = self.landing_rate().unwrap_or(0.0);
+        let fee = outcome.fee.max(self.fee);
+        let min_step = self.min_step();
+        self.fee = if !outcome.landed {
+            // Missed: raise quickly
+            fee.saturating_add((fee / 2).max(min_step))
+        } else if self.outcomes.len() >= MIN_SAMPLES
+            && rate >= self.target_landing_rate
+            && outcome.time_to_confirm <= SLOW_CONFIRM
+        {
+            // Landing comfortably: ease off
+            self.fee.saturating_sub((self.fee / 10).max(min_step))
+        } else if rate < self.target_landing_rate {
+            // Landing, but not often enough: nudge up
+            self.fee.saturating_add((self.fee / 10).max(min_step))
+        } else {
+            self.fee
+        }
+        .clamp(self.floor, self.cap);
+    }
+
+    /// Smallest step the fee moves by, scaled to the cap so that small caps
+    /// are approached gradually instead of in a single step.
+    fn min_step(&self) -> u64 {
+        (self.cap.saturating_mul(MIN_STEP_PERCENT) / 100).max(MIN_STEP)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(controller: &FeeController, landed: bool) -> FeeOutcome {
+        FeeOutcome {
+            fee: controller.fee(),
+            landed,
+            time_to_confirm: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn escalates_on_misses_up_to_the_cap() {
+        let mut controller = FeeController::new(0, 100_000, 0.8);
+        let mut last = controller.fee();
+        for _ in 0..5 {
+            controller.record(outcome(&controller, false));
+            assert!(controller.fee() > last);
+            last = controller.fee();
+        }
+        for _ in 0..50 {
+            controller.record(outcome(&controller, false));
+        }
+        assert_eq!(controller.fee(), 100_000);
+    }
+
+    #[test]
+    fn decays_on_fast_landings_down_to_the_floor() {
+        let mut controller = FeeController::new(500, 100_000, 0.8);
+        for _ in 0..20 {
+            controller.record(outcome(&controller, false));
+        }
+        assert_eq!(controller.fee(), 100_000);
+
+        // Decay only starts once the window shows the target landing rate
+        let mut decayed = false;
+        let mut last = controller.fee();
+        for _ in 0..200 {
+            controller.record(outcome(&controller, true));
+            assert!(controller.fee() <= last);
+            decayed |= controller.fee() < last;
+            last = controller.fee();
+        }
+        assert!(decayed);
+        assert_eq!(controller.fee(), 500);
+    }
+
+    #[test]
+    fn small_caps_are_approached_gradually() {
+        let mut controller = FeeController::new(0, 1_000, 0.8);
+        controller.record(outcome(&controller, false));
+        assert!(controller.fee() > 0);
+        assert!(controller.fee() < 1_000);
+    }
+
+    #[test]
+    fn slow_landings_do_not_lower_the_fee() {
+        let mut controller = FeeController::new(0, 100_000, 0.5);
+        for _ in 0..10 {
+            controller.record(outcome(&controller, false));
+        }
+        let fee = controller.fee();
+        for _ in 0..20 {
+            controller.record(FeeOutcome {
+                fee: controller.fee(),
+                landed: true,
+                time_to_confirm: SLOW_CONFIRM + Duration::from_secs(1),
+            });
+        }
+        assert!(controller.fee() >= fee);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use colored::*;
+use indicatif::ProgressBar;
+use log::{debug, error, warn};
+use solana_program::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_sdk::{signature::Signer, transaction::Transaction};
+
+use crate::{error::Error, signer::DynSigner, utils::get_latest_blockhash_with_retries, Miner};
+
+/// Balance below which a fee payer is considered out of funds.
+pub const MIN_ETH_BALANCE: f64 = 0.0005;
+
+/// How long to wait before checking an exhausted fee payer again.
+const BALANCE_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The fee payer signers, of which one pays at a time. When it runs low the
+/// next one with enough ETH takes over, and if none has any left, the active
+/// one is topped up from the funding wallet, if configured.
+pub struct FeePayerPool {
+    signers: Vec<DynSigner>,
+    active: AtomicUsize,
+    funding: Option<DynSigner>,
+    top_up: u64,
+}
+
+impl FeePayerPool {
+    pub fn new(signers: Vec<DynSigner>, funding: Option<DynSigner>, top_up: f64) -> Self {
+        Self {
+            signers,
+            active: AtomicUsize::new(0),
+            funding,
+            top_up: sol_to_lamports(top_up),
+        }
+    }
+
+    /// The signer currently paying fees.
+    pub fn active(&self) -> DynSigner {
+        self.signers[self.active.load(Ordering::Relaxed) % self.signers.len()].clone()
+    }
+}
+
+impl Miner {
+    /// Makes sure the active fee payer can pay for a transaction, rotating to
+    /// another fee payer or topping it up if needed. If none can pay, mining waits
+    /// for funds rather than stopping, while other commands fail.
+    pub async fn check_balance(&self, progress_bar: &ProgressBar, wait: bool) -> Result<(), Error> {
+        let min_balance = sol_to_lamports(MIN_ETH_BALANCE);
+        loop {
+            let fee_payer = self.fee_payer().pubkey();
+            debug!("Checking balance for fee payer: {}", fee_payer);
+            let balance = match self.rpc_client.get_balance(&fee_payer).await {
+                Ok(balance) => balance,
+                Err(err) => {
+                    // Let the send itself surface rpc problems
+                    warn!("Error fetching fee payer balance: {}", err);
+                    return Ok(());
+                }
+            };
+            debug!("Current balance: {} ETH", lamports_to_sol(balance));
+            if balance >= min_balance {
+                return Ok(());
+            }
+
+            // Rotate to the next fee payer with enough funds
+            if self.rotate_fee_payer(min_balance).await {
+                progress_bar.println(format!(
+                    "  {} Fee payer {} is low on ETH. Switched to {}",
+                    "WARNING".bold().yellow(),
+                    fee_payer,
+                    self.fee_payer().pubkey()
+                ));
+                return Ok(());
+            }
+
+            // Top up the active fee payer
+            if let Some(ref funding) = self.fee_payers.funding {
+                match self.top_up_fee_payer(funding, fee_payer).await {
+                    Ok(()) => {
+                        progress_bar.println(format!(
+                            "  Topped up fee payer {} with {} ETH",
+                            fee_payer,
+                            lamports_to_sol(self.fee_payers.top_up)
+                        ));
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!("Error topping up fee payer {}: {}", fee_payer, err);
+                        progress_bar.println(format!(
+                            "  {} Failed to top up fee payer: {}",
+                            "ERROR".bold().red(),
+                            err
+                        ));
+                    }
+                }
+            }
+
+            error!(
+                "Insufficient balance: {} ETH < {} ETH",
+                lamports_to_sol(balance),
+                MIN_ETH_BALANCE
+            );
+            if !wait {
+                return Err(Error::InsufficientBalance(
+                    fee_payer,
+                    lamports_to_sol(balance),
+                ));
+            }
+            progress_bar.println(format!(
+                "  {} Fee payer {} has {} ETH. Retrying in {}s...",
+                "WARNING".bold().yellow(),
+                fee_payer,
+                lamports_to_sol(balance),
+                BALANCE_RETRY_DELAY.as_secs()
+            ));
+            tokio::time::sleep(BALANCE_RETRY_DELAY).await;
+        }
+    }
+
+    /// Makes the next fee payer with at least `min_balance` the active one.
+    /// Returns false if there is none.
+    async fn rotate_fee_payer(&self, min_balance: u64) -> bool {
+        let pool = &self.fee_payers;
+        let active = pool.active.load(Ordering::Relaxed);
+        for offset in 1..pool.signers.len() {
+            let index = (active + offset) % pool.signers.len();
+            let pubkey = pool.signers[index].pubkey();
+            if let Ok(balance) = self.rpc_client.get_balance(&pubkey).await {
+                if balance >= min_balance {
+                    pool.active.store(index, Ordering::Relaxed);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Transfers the top-up amount from the funding wallet to the fee payer.
+    async fn top_up_fee_payer(&self, funding: &DynSigner, fee_payer: Pubkey) -> Result<(), Error> {
+        let ix =
+            system_instruction::transfer(&funding.pubkey(), &fee_payer, self.fee_payers.top_up);
+        let (hash, _) = get_latest_blockhash_with_retries(&self.rpc_client).await?;
+        let mut tx = Transaction::new_with_payer(&[ix], Some(&funding.pubkey()));
+        tx.try_sign(&[funding], hash)?;
+        let sig = self.rpc_client.send_and_confirm_transaction(&tx).await?;
+        debug!("Topped up fee payer {}: {}", fee_payer, sig);
+        Ok(())
+    }
+}
@@ -1,2 +1,12 @@
+mod fee_controller;
+mod fee_payer;
+mod offline;
 mod send_and_confirm;
-mod priority_fee;
\ No newline at end of file
+mod priority_fee;
+mod retry_policy;
+
+pub use fee_controller::*;
+pub use fee_payer::*;
+pub use offline::*;
+pub use priority_fee::*;
+pub use retry_policy::*;
@@ -0,0 +1,258 @@
+use std::{fs, path::Path, str::FromStr};
+
+use colored::*;
+use serde::{Deserialize, Serialize};
+use solana_program::{
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    system_instruction::{self, SystemInstruction},
+    system_program,
+};
+use solana_rpc_client::spinner;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Signature, Signer},
+    transaction::Transaction,
+};
+
+use crate::{
+    args::OfflineArgs,
+    error::Error,
+    signer::{load_signer, DynSigner},
+    utils::{get_latest_blockhash_with_retries, get_nonce, ComputeBudget},
+    Miner,
+};
+
+use super::send_and_confirm::MAX_COMPUTE_UNITS;
+
+/// A transaction written by `--sign-only` or `--export-unsigned`, to be
+/// completed and broadcast by `bitz tx submit`.
+#[derive(Serialize, Deserialize)]
+pub struct OfflineTransaction {
+    pub blockhash: String,
+    pub signers: Vec<OfflineSigner>,
+    /// Base58 encoded, bincode serialized transaction.
+    pub transaction: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OfflineSigner {
+    pub pubkey: String,
+    pub signed: bool,
+}
+
+impl OfflineTransaction {
+    pub fn new(tx: &Transaction) -> Result<Self, Error> {
+        let data = bincode::serialize(tx)
+            .map_err(|err| Error::Internal(format!("failed to serialize transaction: {}", err)))?;
+        Ok(Self {
+            blockhash: tx.message.recent_blockhash.to_string(),
+            signers: required_signers(tx)
+                .into_iter()
+                .map(|(pubkey, signature)| OfflineSigner {
+                    pubkey: pubkey.to_string(),
+                    signed: signature != Signature::default(),
+                })
+                .collect(),
+            transaction: bs58::encode(data).into_string(),
+        })
+    }
+
+    pub fn transaction(&self) -> Result<Transaction, Error> {
+        let data = bs58::decode(&self.transaction)
+            .into_vec()
+            .map_err(|err| Error::Internal(format!("invalid transaction encoding: {}", err)))?;
+        bincode::deserialize(&data)
+            .map_err(|err| Error::Internal(format!("failed to deserialize transaction: {}", err)))
+    }
+
+    pub fn read(path: &str) -> Result<Self, Error> {
+        let data = fs::read_to_string(path)
+            .map_err(|err| Error::Internal(format!("failed to read {}: {}", path, err)))?;
+        serde_json::from_str(&data)
+            .map_err(|err| Error::Internal(format!("failed to parse {}: {}", path, err)))
+    }
+
+    pub fn print_signers(&self) {
+        for signer in self.signers.iter() {
+            println!(
+                "Signer: {} {}",
+                signer.pubkey,
+                if signer.signed {
+                    "signed".green()
+                } else {
+                    "missing".yellow()
+                }
+            );
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Error> {
+        let data =
+            serde_json::to_string_pretty(self).map_err(|err| Error::Internal(err.to_string()))?;
+        fs::write(path, data)
+            .map_err(|err| Error::Internal(format!("failed to write {}: {}", path, err)))
+    }
+}
+
+/// The pubkeys that must sign the transaction, with their current signatures.
+pub fn required_signers(tx: &Transaction) -> Vec<(Pubkey, Signature)> {
+    let num_signers = tx.message.header.num_required_signatures as usize;
+    tx.message
+        .account_keys
+        .iter()
+        .take(num_signers)
+        .copied()
+        .zip(tx.signatures.iter().copied())
+        .collect()
+}
+
+impl Miner {
+    /// Builds the transaction that `send_and_confirm` would send and writes it to
+    /// the `--output` file, signed by the local keypairs with `--sign-only` or
+    /// unsigned with `--export-unsigned`.
+    pub async fn export_transaction(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        offline: &OfflineArgs,
+    ) -> Result<(), Error> {
+        let signer = self.signer();
+        let fee_payer = self.fee_payer();
+
+        // Without a blockhash, the rpc is reachable and can be used to size the budget
+        let cus = match compute_budget {
+            ComputeBudget::Fixed(cus) => cus,
+            ComputeBudget::Dynamic if offline.blockhash.is_some() => MAX_COMPUTE_UNITS,
+            ComputeBudget::Dynamic => {
+                let progress_bar = spinner::new_progress_bar();
+                progress_bar.set_message("Simulating transaction...");
+                let cus = self
+                    .simulate_compute_units(ixs, &fee_payer.pubkey(), &[], &progress_bar)
+                    .await?;
+                progress_bar.finish_and_clear();
+                cus
+            }
+        };
+        let nonce = match offline.nonce {
+            Some(ref nonce) => Some(Pubkey::from_str(nonce)?),
+            None => None,
+        };
+        let (nonce_authority, nonce_signer) = match offline.nonce_authority {
+            Some(ref authority) => nonce_authority(authority, self.proxy.as_deref())?,
+            None => (signer.pubkey(), Some(signer.clone())),
+        };
+        let mut final_ixs = vec![];
+
+        // Advancing the nonce must be the first instruction
+        if let Some(nonce) = nonce {
+            final_ixs.push(system_instruction::advance_nonce_account(
+                &nonce,
+                &nonce_authority,
+            ));
+        }
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus));
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+            self.priority_fee.unwrap_or(0),
+        ));
+        final_ixs.extend_from_slice(ixs);
+
+        // Build the tx against the given blockhash, or the stored nonce or latest blockhash
+        let hash = match (offline.blockhash.as_ref(), nonce) {
+            (Some(hash), _) => Hash::from_str(hash)
+                .map_err(|err| Error::Internal(format!("invalid blockhash {}: {}", hash, err)))?,
+            (None, Some(nonce)) => get_nonce(&self.rpc_client, nonce)
+                .await
+                .map_err(|err| Error::Internal(err.to_string()))?
+                .blockhash(),
+            (None, None) => get_latest_blockhash_with_retries(&self.rpc_client).await?.0,
+        };
+        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
+        if offline.sign_only {
+            let mut signers = vec![&signer, &fee_payer];
+            signers.extend(nonce_signer.as_ref());
+            sign_with(&mut tx, &signers, hash)?;
+        } else {
+            tx.message.recent_blockhash = hash;
+        }
+
+        // Write to file
+        let offline_tx = OfflineTransaction::new(&tx)?;
+        offline_tx.write(&offline.output)?;
+        match nonce {
+            Some(nonce) => println!("Nonce: {} ({})", offline_tx.blockhash, nonce),
+            None => println!("Blockhash: {}", offline_tx.blockhash),
+        }
+        offline_tx.print_signers();
+        println!("Wrote transaction to {}", offline.output);
+        if offline.export_unsigned {
+            println!("Sign it with `bitz tx sign {}`", offline.output);
+        }
+        println!("Submit it with `bitz tx submit {}`", offline.output);
+        Ok(())
+    }
+}
+
+/// The error for account state that can't be fetched when building a
+/// transaction with `--blockhash`.
+pub fn required_offline(what: &str) -> Error {
+    Error::Internal(format!(
+        "{} is required with --blockhash, since it can't be fetched offline",
+        what
+    ))
+}
+
+/// Resolves `--nonce-authority` to the authority's pubkey, and its signer unless
+/// it was given as a pubkey to sign with later.
+fn nonce_authority(
+    authority: &str,
+    proxy: Option<&str>,
+) -> Result<(Pubkey, Option<DynSigner>), Error> {
+    if !Path::new(authority).exists() {
+        if let Ok(pubkey) = Pubkey::from_str(authority) {
+            return Ok((pubkey, None));
+        }
+    }
+    let signer = load_signer(authority, proxy)?;
+    Ok((signer.pubkey(), Some(signer)))
+}
+
+/// The durable nonce account the transaction advances, if it signs against one
+/// instead of a recent blockhash.
+pub fn nonce_account(tx: &Transaction) -> Option<Pubkey> {
+    let ix = tx.message.instructions.first()?;
+    let program_id = tx.message.account_keys.get(ix.program_id_index as usize)?;
+    if *program_id != system_program::ID {
+        return None;
+    }
+    match bincode::deserialize::<SystemInstruction>(&ix.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => ix
+            .accounts
+            .first()
+            .and_then(|index| tx.message.account_keys.get(*index as usize))
+            .copied(),
+        _ => None,
+    }
+}
+
+/// Signs the transaction with those of the signers that are required signers.
+/// Other signatures are left as they are.
+pub fn sign_with(tx: &mut Transaction, signers: &[&DynSigner], hash: Hash) -> Result<(), Error> {
+    let required = required_signers(tx);
+    let mut signed = vec![];
+    let signers: Vec<&DynSigner> = signers
+        .iter()
+        .copied()
+        .filter(|signer| {
+            let pubkey = signer.pubkey();
+            if signed.contains(&pubkey) || !required.iter().any(|(key, _)| *key == pubkey) {
+                return false;
+            }
+            signed.push(pubkey);
+            true
+        })
+        .collect();
+    tx.try_partial_sign(&signers, hash)
+        .map_err(|err| Error::Internal(format!("failed to sign transaction: {}", err)))
+}
@@ -1,56 +1,192 @@
 use crate::Miner;
 
 use eore_api::consts::BUS_ADDRESSES;
-use reqwest::Client;
+use futures::future::BoxFuture;
 use serde_json::{json, Value};
 
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
-use std::{collections::HashMap, str::FromStr};
+use std::sync::{Arc, Mutex};
 
 use solana_client::rpc_response::RpcPrioritizationFee;
 
 use url::Url;
-enum FeeStrategy {
+
+/// Where dynamic priority fee estimates come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FeeStrategy {
+    /// Pick a provider from the fee url's host, falling back to local.
+    Auto,
     Helius,
     Triton,
-    LOCAL,
     Alchemy,
     Quiknode,
+    /// `getRecentPrioritizationFees` on the primary rpc.
+    Local,
+    /// A custom JSON-RPC method, see `--fee-rpc-method`.
+    Custom,
+}
+
+/// A source of priority fee estimates, in microlamports per compute unit.
+pub trait FeeEstimator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Estimates the fee. Sources returning raw samples take the given percentile.
+    fn estimate(&self, percentile: u8) -> BoxFuture<'_, Result<u64, String>>;
+}
+
+/// The configured fee estimator, plus the percentile and smoothing applied to
+/// its estimates.
+pub struct DynamicFee {
+    estimator: Box<dyn FeeEstimator>,
+    fallback: Option<Box<dyn FeeEstimator>>,
+    percentile: u8,
+    smoothing: f64,
+    last: Mutex<Option<f64>>,
+}
+
+impl DynamicFee {
+    pub fn new(
+        strategy: FeeStrategy,
+        fee_url: String,
+        rpc_method: Option<String>,
+        percentile: u8,
+        smoothing: f64,
+        rpc_client: Arc<RpcClient>,
+        http_client: reqwest::Client,
+    ) -> Result<Self, String> {
+        let local = Box::new(LocalEstimator { rpc_client }) as Box<dyn FeeEstimator>;
+        let strategy = match strategy {
+            FeeStrategy::Auto => detect_strategy(&fee_url),
+            strategy => strategy,
+        };
+        let remote = |kind| -> Box<dyn FeeEstimator> {
+            Box::new(RemoteEstimator {
+                kind,
+                http_client: http_client.clone(),
+                url: fee_url.clone(),
+            })
+        };
+        let (estimator, fallback) = match strategy {
+            FeeStrategy::Local | FeeStrategy::Auto => (local, None),
+            FeeStrategy::Helius => (remote(RemoteKind::Helius), Some(local)),
+            FeeStrategy::Triton => (remote(RemoteKind::Triton), Some(local)),
+            FeeStrategy::Alchemy => (remote(RemoteKind::Alchemy), Some(local)),
+            FeeStrategy::Quiknode => (remote(RemoteKind::Quiknode), Some(local)),
+            FeeStrategy::Custom => {
+                let method =
+                    rpc_method.ok_or("--fee-rpc-method is required for the custom fee strategy")?;
+                (remote(RemoteKind::Custom(method)), Some(local))
+            }
+        };
+        Ok(Self {
+            estimator,
+            fallback,
+            percentile: percentile.clamp(1, 100),
+            smoothing: smoothing.clamp(0.01, 1.0),
+            last: Mutex::new(None),
+        })
+    }
+
+    /// Fetches an estimate, falling back to the local estimator if the provider
+    /// fails, and smooths it with an exponential moving average.
+    pub async fn estimate(&self) -> Result<u64, String> {
+        let fee = match self.estimator.estimate(self.percentile).await {
+            Ok(fee) => fee,
+            Err(err) => match self.fallback {
+                Some(ref fallback) => {
+                    fallback
+                        .estimate(self.percentile)
+                        .await
+                        .map_err(|fallback_err| {
+                            format!(
+                                "{} estimate failed: {}; {} estimate failed: {}",
+                                self.estimator.name(),
+                                err,
+                                fallback.name(),
+                                fallback_err
+                            )
+                        })?
+                }
+                None => {
+                    return Err(format!(
+                        "{} estimate failed: {}",
+                        self.estimator.name(),
+                        err
+                    ))
+                }
+            },
+        };
+        let mut last = self.last.lock().unwrap();
+        let smoothed = smooth(*last, fee, self.smoothing);
+        *last = Some(smoothed);
+        Ok(smoothed.round() as u64)
+    }
 }
 
 impl Miner {
     pub async fn get_dynamic_priority_fee(&self) -> Result<u64, String> {
-        // Get url
-        let rpc_url = self
-            .dynamic_fee_url
-            .clone()
-            .unwrap_or(self.rpc_client.url());
-
-        // Select fee estiamte strategy
-        let host = Url::parse(&rpc_url)
-            .unwrap()
-            .host_str()
-            .unwrap()
-            .to_string();
-        let strategy = if host.contains("helius-rpc.com") {
-            FeeStrategy::Helius
-        } else if host.contains("alchemy.com") {
-            FeeStrategy::Alchemy
-        } else if host.contains("quiknode.pro") {
-            FeeStrategy::Quiknode
-        } else if host.contains("rpcpool.com") {
-            FeeStrategy::Triton
-        } else {
-            FeeStrategy::LOCAL
+        let Some(ref fee_estimator) = self.fee_estimator else {
+            return Err("Dynamic fees are not enabled".to_string());
         };
+        let fee = fee_estimator.estimate().await?;
 
-        // Build fee estimate request
-        let client = Client::new();
-        let ore_addresses: Vec<String> = std::iter::once(eore_api::ID.to_string())
-            .chain(BUS_ADDRESSES.iter().map(|pubkey| pubkey.to_string()))
-            .collect();
-        let body = match strategy {
-            FeeStrategy::Helius => Some(json!({
+        // Check if the calculated fee is higher than max
+        if let Some(max_fee) = self.priority_fee {
+            Ok(fee.min(max_fee))
+        } else {
+            Ok(fee)
+        }
+    }
+}
+
+/// Selects a provider by host name, defaulting to local estimates.
+fn detect_strategy(fee_url: &str) -> FeeStrategy {
+    let Some(host) = Url::parse(fee_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+    else {
+        return FeeStrategy::Local;
+    };
+    if host.contains("helius-rpc.com") {
+        FeeStrategy::Helius
+    } else if host.contains("alchemy.com") {
+        FeeStrategy::Alchemy
+    } else if host.contains("quiknode.pro") {
+        FeeStrategy::Quiknode
+    } else if host.contains("rpcpool.com") {
+        FeeStrategy::Triton
+    } else {
+        FeeStrategy::Local
+    }
+}
+
+fn ore_addresses() -> Vec<String> {
+    std::iter::once(eore_api::ID.to_string())
+        .chain(BUS_ADDRESSES.iter().map(|pubkey| pubkey.to_string()))
+        .collect()
+}
+
+enum RemoteKind {
+    Helius,
+    Triton,
+    Alchemy,
+    Quiknode,
+    Custom(String),
+}
+
+/// Estimates fees through a provider's JSON-RPC api.
+struct RemoteEstimator {
+    kind: RemoteKind,
+    http_client: reqwest::Client,
+    url: String,
+}
+
+impl RemoteEstimator {
+    fn body(&self, percentile: u8) -> Value {
+        let ore_addresses = ore_addresses();
+        match self.kind {
+            RemoteKind::Helius => json!({
                 "jsonrpc": "2.0",
                 "id": "priority-fee-estimate",
                 "method": "getPriorityFeeEstimate",
@@ -60,164 +196,191 @@ impl Miner {
                         "recommended": true
                     }
                 }]
-            })),
-            FeeStrategy::Alchemy => Some(json!({
+            }),
+            RemoteKind::Alchemy => json!({
                 "jsonrpc": "2.0",
                 "id": "priority-fee-estimate",
                 "method": "getRecentPrioritizationFees",
                 "params": [
                     ore_addresses
                 ]
-            })),
-            FeeStrategy::Quiknode => Some(json!({
+            }),
+            RemoteKind::Quiknode => json!({
                 "jsonrpc": "2.0",
                 "id": "1",
                 "method": "qn_estimatePriorityFees",
                 "params": {
-                    "account": "EorefDWqzJK31vLxaqkDGsx3CRKqPVpWfuJL7qBQMZYd",
+                    "account": eore_api::ID.to_string(),
                     "last_n_blocks": 100
                 }
-            })),
-            FeeStrategy::Triton => Some(json!({
+            }),
+            RemoteKind::Triton => json!({
                 "jsonrpc": "2.0",
                 "id": "priority-fee-estimate",
                 "method": "getRecentPrioritizationFees",
                 "params": [
                     ore_addresses,
                     {
-                        "percentile": 5000,
+                        "percentile": percentile as u64 * 100,
                     }
                 ]
-            })),
-            FeeStrategy::LOCAL => None,
+            }),
+            RemoteKind::Custom(ref method) => json!({
+                "jsonrpc": "2.0",
+                "id": "priority-fee-estimate",
+                "method": method,
+                "params": [
+                    ore_addresses
+                ]
+            }),
+        }
+    }
+
+    fn parse(&self, response: &Value, percentile: u8) -> Result<u64, String> {
+        let result = &response["result"];
+        let fee = match self.kind {
+            RemoteKind::Helius => result["priorityFeeEstimate"].as_f64().map(|fee| fee as u64),
+            RemoteKind::Quiknode => {
+                if result.is_null() {
+                    return Err("Please enable the Solana Priority Fee API add-on in your QuickNode account.".to_string());
+                }
+                result["per_compute_unit"]["medium"]
+                    .as_f64()
+                    .map(|fee| fee as u64)
+            }
+            RemoteKind::Alchemy => {
+                serde_json::from_value::<Vec<RpcPrioritizationFee>>(result.clone())
+                    .ok()
+                    .and_then(|fees| {
+                        let fees: Vec<u64> =
+                            fees.iter().map(|fee| fee.prioritization_fee).collect();
+                        percentile_of(&fees, percentile)
+                    })
+            }
+            RemoteKind::Triton => {
+                serde_json::from_value::<Vec<RpcPrioritizationFee>>(result.clone())
+                    .ok()
+                    .and_then(estimate_prioritization_fee_microlamports)
+            }
+            RemoteKind::Custom(_) => parse_fee_value(result, percentile),
         };
+        fee.ok_or_else(|| format!("Failed to parse priority fee response: {:?}", response))
+    }
+}
+
+impl FeeEstimator for RemoteEstimator {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            RemoteKind::Helius => "helius",
+            RemoteKind::Triton => "triton",
+            RemoteKind::Alchemy => "alchemy",
+            RemoteKind::Quiknode => "quiknode",
+            RemoteKind::Custom(_) => "custom",
+        }
+    }
 
-        // Send rpc request
-        let response = if let Some(body) = body {
-            let response: Value = client
-                .post(rpc_url)
-                .json(&body)
+    fn estimate(&self, percentile: u8) -> BoxFuture<'_, Result<u64, String>> {
+        Box::pin(async move {
+            let response: Value = self
+                .http_client
+                .post(&self.url)
+                .json(&self.body(percentile))
                 .send()
                 .await
-                .unwrap()
+                .map_err(|err| err.to_string())?
                 .json()
                 .await
-                .unwrap();
-            response
-        } else {
-            Value::Null
-        };
+                .map_err(|err| err.to_string())?;
+            if let Some(err) = response.get("error") {
+                return Err(format!("Fee estimate request failed: {}", err));
+            }
+            self.parse(&response, percentile)
+        })
+    }
+}
 
-        // Parse response
-        let calculated_fee = match strategy {
-            FeeStrategy::Helius => response["result"]["priorityFeeEstimate"]
-                .as_f64()
-                .map(|fee| fee as u64)
-                .ok_or_else(|| format!("Failed to parse priority fee response: {:?}", response)),
-            FeeStrategy::Quiknode => response["result"]["per_compute_unit"]["medium"]
-                .as_f64()
-                .map(|fee| fee as u64)
-                .ok_or_else(|| format!("Please enable the Solana Priority Fee API add-on in your QuickNode account.")),
-            FeeStrategy::Alchemy => response["result"]
-                .as_array()
-                .and_then(|arr| {
-                    Some(
-                        arr.into_iter()
-                            .map(|v| v["prioritizationFee"].as_u64().unwrap())
-                            .collect::<Vec<u64>>(),
-                    )
-                })
-                .and_then(|fees| {
-                    Some(
-                        ((fees.iter().sum::<u64>() as f32 / fees.len() as f32).ceil() * 1.2) as u64,
-                    )
-                })
-                .ok_or_else(|| format!("Failed to parse priority fee response: {:?}", response)),
-            FeeStrategy::Triton => {
-                serde_json::from_value::<Vec<RpcPrioritizationFee>>(response["result"].clone())
-                    .map(|prioritization_fees| {
-                        estimate_prioritization_fee_microlamports(prioritization_fees)
-                    })
-                    .or_else(|error: serde_json::Error| {
-                        Err(format!(
-                            "Failed to parse priority fee response: {response:?}, error: {error}"
-                        ))
-                    })
-            },
-            FeeStrategy::LOCAL => {
-                self.local_dynamic_fee().await.or_else(|err| {
-                    Err(format!("Failed to parse priority fee response: {err}"))
-                })
-            },
-        };
+/// Number of recent slots local estimates are taken over.
+const LOCAL_FEE_SLOTS: usize = 450;
 
-        // Check if the calculated fee is higher than max
-        match calculated_fee {
-            Err(err) => Err(err),
-            Ok(fee) => {
-                if let Some(max_fee) = self.priority_fee {
-                    Ok(fee.min(max_fee))
-                } else {
-                    Ok(fee)
-                }
-            }
-        }
+/// Estimates fees from `getRecentPrioritizationFees` on the primary rpc.
+struct LocalEstimator {
+    rpc_client: Arc<RpcClient>,
+}
+
+impl FeeEstimator for LocalEstimator {
+    fn name(&self) -> &'static str {
+        "local"
     }
 
-    pub async fn local_dynamic_fee(&self) -> Result<u64, Box<dyn std::error::Error>> {
-        let client = self.rpc_client.clone();
-        let pubkey = [
-            "EorefDWqzJK31vLxaqkDGsx3CRKqPVpWfuJL7qBQMZYd",
-            "5HngGmYzvSuh3XyU11brHDpMTHXQQRQQT4udGFtQSjgR",
-            "2oLNTQKRb4a2117kFi6BYTUDu3RPrMVAHFhCfPKMosxX",
-        ];
-        let address_strings = pubkey;
-
-        // Convert strings to Pubkey
-        let addresses: Vec<Pubkey> = address_strings
-            .into_iter()
-            .map(|addr_str| Pubkey::from_str(addr_str).expect("Invalid address"))
-            .collect();
-
-        // Get recent prioritization fees
-        let recent_prioritization_fees = client.get_recent_prioritization_fees(&addresses).await?;
-        if recent_prioritization_fees.is_empty() {
-            return Err("No recent prioritization fees".into());
-        }
-        let mut sorted_fees: Vec<_> = recent_prioritization_fees.into_iter().collect();
-        sorted_fees.sort_by(|a, b| b.slot.cmp(&a.slot));
-        let chunk_size = 150;
-        let chunks: Vec<_> = sorted_fees.chunks(chunk_size).take(3).collect();
-        let mut percentiles: HashMap<u8, u64> = HashMap::new();
-        for (_, chunk) in chunks.iter().enumerate() {
-            let fees: Vec<u64> = chunk.iter().map(|fee| fee.prioritization_fee).collect();
-            percentiles = Self::calculate_percentiles(&fees);
-        }
+    fn estimate(&self, percentile: u8) -> BoxFuture<'_, Result<u64, String>> {
+        Box::pin(async move {
+            let addresses: Vec<Pubkey> = std::iter::once(eore_api::ID)
+                .chain(BUS_ADDRESSES.iter().copied())
+                .collect();
 
-        // Default to 75 percentile
-        let fee = *percentiles.get(&75).unwrap_or(&0);
-        Ok(fee)
-    }
-
-    fn calculate_percentiles(fees: &[u64]) -> HashMap<u8, u64> {
-        let mut sorted_fees = fees.to_vec();
-        sorted_fees.sort_unstable();
-        let len = sorted_fees.len();
-        let percentiles = vec![10, 25, 50, 60, 70, 75, 80, 85, 90, 100];
-        percentiles
-            .into_iter()
-            .map(|p| {
-                let index = (p as f64 / 100.0 * len as f64).round() as usize;
-                (p, sorted_fees[index.saturating_sub(1)])
-            })
-            .collect()
+            // Get recent prioritization fees
+            let mut recent_prioritization_fees = self
+                .rpc_client
+                .get_recent_prioritization_fees(&addresses)
+                .await
+                .map_err(|err| err.to_string())?;
+
+            // Take the percentile over the most recent slots
+            recent_prioritization_fees.sort_by_key(|fee| std::cmp::Reverse(fee.slot));
+            let fees: Vec<u64> = recent_prioritization_fees
+                .iter()
+                .take(LOCAL_FEE_SLOTS)
+                .map(|fee| fee.prioritization_fee)
+                .collect();
+            percentile_of(&fees, percentile).ok_or("No recent prioritization fees".to_string())
+        })
+    }
+}
+
+/// Interprets a custom method's result: a bare number, an object carrying a
+/// `priorityFeeEstimate`, or a list of numbers or `prioritizationFee` entries.
+fn parse_fee_value(value: &Value, percentile: u8) -> Option<u64> {
+    if let Some(fee) = value.as_f64() {
+        return Some(fee as u64);
+    }
+    if let Some(fee) = value["priorityFeeEstimate"].as_f64() {
+        return Some(fee as u64);
+    }
+    let fees: Vec<u64> = value
+        .as_array()?
+        .iter()
+        .filter_map(|v| {
+            v.as_f64()
+                .or_else(|| v["prioritizationFee"].as_f64())
+                .map(|fee| fee as u64)
+        })
+        .collect();
+    percentile_of(&fees, percentile)
+}
+
+/// Nearest-rank percentile of the samples, if any.
+fn percentile_of(fees: &[u64], percentile: u8) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    let mut sorted_fees = fees.to_vec();
+    sorted_fees.sort_unstable();
+    let index = (percentile as f64 / 100.0 * sorted_fees.len() as f64).ceil() as usize;
+    Some(sorted_fees[index.clamp(1, sorted_fees.len()) - 1])
+}
+
+/// Exponential moving average of the estimates, seeded with the first one.
+fn smooth(prev: Option<f64>, fee: u64, smoothing: f64) -> f64 {
+    match prev {
+        Some(prev) => smoothing * fee as f64 + (1.0 - smoothing) * prev,
+        None => fee as f64,
     }
 }
 
 /// Our estimate is the average over the last 20 slots
 fn estimate_prioritization_fee_microlamports(
     prioritization_fees: Vec<RpcPrioritizationFee>,
-) -> u64 {
+) -> Option<u64> {
     let prioritization_fees = prioritization_fees
         .into_iter()
         .rev()
@@ -229,11 +392,132 @@ fn estimate_prioritization_fee_microlamports(
         )
         .collect::<Vec<_>>();
     if prioritization_fees.is_empty() {
-        panic!("Response does not contain any prioritization fees");
+        return None;
     }
 
     let prioritization_fee =
         prioritization_fees.iter().sum::<u64>() / prioritization_fees.len() as u64;
 
-    prioritization_fee
+    Some(prioritization_fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    #[test]
+    fn percentile_of_takes_the_nearest_rank() {
+        let fees = [50, 10, 40, 20, 30];
+        for (percentile, expected) in [
+            (1, 10),
+            (20, 10),
+            (25, 20),
+            (50, 30),
+            (75, 40),
+            (90, 50),
+            (100, 50),
+        ] {
+            assert_eq!(
+                percentile_of(&fees, percentile),
+                Some(expected),
+                "p{}",
+                percentile
+            );
+        }
+        assert_eq!(percentile_of(&[7], 50), Some(7));
+        assert_eq!(percentile_of(&[], 50), None);
+    }
+
+    #[test]
+    fn parse_fee_value_accepts_each_result_shape() {
+        let cases = [
+            (json!(1234), Some(1234)),
+            (json!(1234.9), Some(1234)),
+            (json!({ "priorityFeeEstimate": 5000.0 }), Some(5000)),
+            (json!([10, 20, 30, 40]), Some(20)),
+            (
+                json!([
+                    { "slot": 1, "prioritizationFee": 100 },
+                    { "slot": 2, "prioritizationFee": 300 },
+                    { "slot": 3, "prioritizationFee": 200 },
+                ]),
+                Some(200),
+            ),
+            (json!([]), None),
+            (json!("high"), None),
+            (json!({ "fee": 10 }), None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_fee_value(&value, 50), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn detect_strategy_matches_provider_hosts() {
+        let cases = [
+            (
+                "https://mainnet.helius-rpc.com/?api-key=x",
+                FeeStrategy::Helius,
+            ),
+            (
+                "https://solana-mainnet.g.alchemy.com/v2/x",
+                FeeStrategy::Alchemy,
+            ),
+            (
+                "https://example.solana-mainnet.quiknode.pro/x/",
+                FeeStrategy::Quiknode,
+            ),
+            ("https://example.rpcpool.com/x", FeeStrategy::Triton),
+            ("https://api.mainnet-beta.solana.com", FeeStrategy::Local),
+            ("not a url", FeeStrategy::Local),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(detect_strategy(url), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn smooth_moves_towards_new_estimates() {
+        assert_eq!(smooth(None, 1000, 0.5), 1000.0);
+        assert_eq!(smooth(Some(1000.0), 2000, 0.5), 1500.0);
+        assert_eq!(smooth(Some(1000.0), 2000, 1.0), 2000.0);
+        assert_eq!(smooth(Some(1000.0), 0, 0.25), 750.0);
+    }
+
+    /// Returns queued estimates, failing once they run out.
+    struct StubEstimator(Mutex<VecDeque<u64>>);
+
+    impl FeeEstimator for StubEstimator {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        fn estimate(&self, _percentile: u8) -> BoxFuture<'_, Result<u64, String>> {
+            let fee = self.0.lock().unwrap().pop_front();
+            Box::pin(async move { fee.ok_or("no estimate".to_string()) })
+        }
+    }
+
+    fn stub(fees: &[u64]) -> Box<dyn FeeEstimator> {
+        Box::new(StubEstimator(Mutex::new(fees.iter().copied().collect())))
+    }
+
+    #[tokio::test]
+    async fn estimate_smooths_and_falls_back() {
+        let fee = DynamicFee {
+            estimator: stub(&[1000, 3000]),
+            fallback: Some(stub(&[5000])),
+            percentile: 50,
+            smoothing: 0.5,
+            last: Mutex::new(None),
+        };
+        assert_eq!(fee.estimate().await, Ok(1000));
+        assert_eq!(fee.estimate().await, Ok(2000));
+
+        // The fallback takes over once the provider fails, and is smoothed too
+        assert_eq!(fee.estimate().await, Ok(3500));
+        assert!(fee.estimate().await.is_err());
+    }
 }
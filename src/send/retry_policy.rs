@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+/// How hard `send_and_confirm` tries to land a transaction before giving up.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Sends before giving up with a timeout error. `None` retries forever.
+    pub max_attempts: Option<usize>,
+    /// Sends between blockhash refreshes, which also re-price the transaction.
+    pub blockhash_refresh: usize,
+    /// How long to poll for confirmation after each send.
+    pub confirm_timeout: Duration,
+    /// Delay before the first retry, doubled on each further retry.
+    pub backoff: Duration,
+    /// Cap on the retry delay.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never gives up. Used for mining, where a landed solution is worth waiting for.
+    pub fn persistent() -> Self {
+        Self {
+            max_attempts: None,
+            blockhash_refresh: 10,
+            confirm_timeout: Duration::from_secs(4),
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// Gives up after roughly one blockhash lifetime. Used for one-off commands
+    /// like transfers, where the user is waiting on the result.
+    pub fn quick() -> Self {
+        Self {
+            max_attempts: Some(5),
+            blockhash_refresh: 5,
+            confirm_timeout: Duration::from_secs(6),
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+
+    /// Applies `--send-attempts` (or `--mining-send-attempts`) and
+    /// `--confirm-timeout` overrides. Zero attempts means retry forever.
+    pub fn with_overrides(
+        mut self,
+        max_attempts: Option<usize>,
+        confirm_timeout: Option<u64>,
+    ) -> Self {
+        if let Some(max_attempts) = max_attempts {
+            self.max_attempts = (max_attempts > 0).then_some(max_attempts);
+        }
+        if let Some(confirm_timeout) = confirm_timeout {
+            self.confirm_timeout = Duration::from_secs(confirm_timeout);
+        }
+        self
+    }
+
+    /// Delay before the given retry (1-based).
+    pub fn backoff_for(&self, retry: usize) -> Duration {
+        let factor = 1u32 << retry.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Some(30),
+            blockhash_refresh: 10,
+            confirm_timeout: Duration::from_secs(4),
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(4),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_attempts_and_timeout() {
+        let policy = RetryPolicy::quick().with_overrides(Some(12), Some(9));
+        assert_eq!(policy.max_attempts, Some(12));
+        assert_eq!(policy.confirm_timeout, Duration::from_secs(9));
+
+        // Zero attempts retries forever
+        let policy = RetryPolicy::quick().with_overrides(Some(0), None);
+        assert_eq!(policy.max_attempts, None);
+        assert_eq!(policy.confirm_timeout, RetryPolicy::quick().confirm_timeout);
+    }
+
+    #[test]
+    fn no_overrides_keep_the_policy() {
+        let policy = RetryPolicy::persistent().with_overrides(None, None);
+        assert_eq!(policy.max_attempts, None);
+        assert_eq!(policy.blockhash_refresh, 10);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff_for(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff_for(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff_for(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff_for(4), Duration::from_secs(4));
+        assert_eq!(policy.backoff_for(100), Duration::from_secs(4));
+        assert_eq!(RetryPolicy::persistent().backoff_for(5), Duration::ZERO);
+    }
+}
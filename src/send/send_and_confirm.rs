@@ -1,39 +1,52 @@
-use std::{str::FromStr, time::Duration};
+use std::time::{Duration, Instant};
 
 use colored::*;
-use eore_api::error::OreError;
+use futures::StreamExt;
 use indicatif::ProgressBar;
 use log::{debug, error, info, warn};
 use solana_client::{
-    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
-    rpc_config::RpcSendTransactionConfig,
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{
+        RpcSendTransactionConfig, RpcSignatureSubscribeConfig, RpcSimulateTransactionConfig,
+    },
+    rpc_response::RpcSignatureResult,
 };
 use solana_program::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
     instruction::Instruction,
-    native_token::{lamports_to_sol, sol_to_lamports},
+    message::{v0, Message, VersionedMessage},
     pubkey::Pubkey,
 };
 use solana_rpc_client::spinner;
 use solana_sdk::{
-    commitment_config::CommitmentLevel,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
     compute_budget::ComputeBudgetInstruction,
     signature::{Signature, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, VersionedTransaction},
 };
 use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
 
-use crate::utils::{get_latest_blockhash_with_retries, ComputeBudget};
+use crate::error::Error;
+use crate::signer::DynSigner;
+use crate::utils::{
+    decode_transaction_error, get_latest_blockhash_with_retries, get_lookup_table, get_nonce,
+    ComputeBudget,
+};
 use crate::Miner;
 
-const MIN_ETH_BALANCE: f64 = 0.0005;
+use super::{nonce_account, FeeOutcome, RetryPolicy};
 
 const RPC_RETRIES: usize = 0;
-const _SIMULATION_RETRIES: usize = 4;
-const GATEWAY_RETRIES: usize = 150;
-const CONFIRM_RETRIES: usize = 8;
+const SIMULATION_RETRIES: usize = 4;
 
 const CONFIRM_DELAY: u64 = 500;
-const GATEWAY_DELAY: u64 = 0;
+const SIMULATION_DELAY: u64 = 500;
+
+/// Per-transaction compute unit cap enforced by the runtime.
+pub(super) const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+/// Headroom added on top of simulated compute units, in percent.
+const COMPUTE_UNIT_MARGIN: u64 = 20;
 
 impl Miner {
     pub async fn send_and_confirm(
@@ -41,27 +54,79 @@ impl Miner {
         ixs: &[Instruction],
         compute_budget: ComputeBudget,
         skip_confirm: bool,
-    ) -> ClientResult<Signature> {
+    ) -> Result<Signature, Error> {
+        let policy = self.retry_policy(RetryPolicy::default());
+        self.send_and_confirm_with(ixs, compute_budget, skip_confirm, policy, false)
+            .await
+    }
+
+    /// Sends with a command specific retry policy, e.g. `RetryPolicy::quick()`
+    /// for commands that should fail fast.
+    pub async fn send_and_confirm_with_policy(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        skip_confirm: bool,
+        policy: RetryPolicy,
+    ) -> Result<Signature, Error> {
+        let policy = self.retry_policy(policy);
+        self.send_and_confirm_with(ixs, compute_budget, skip_confirm, policy, false)
+            .await
+    }
+
+    /// Sends a mining transaction. Its priority fee is set by the adaptive fee
+    /// controller, if enabled, which learns from the outcome.
+    pub async fn send_and_confirm_mining(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        skip_confirm: bool,
+    ) -> Result<Signature, Error> {
+        let policy = RetryPolicy::persistent()
+            .with_overrides(self.mining_send_attempts, self.confirm_timeout);
+        self.send_and_confirm_with(ixs, compute_budget, skip_confirm, policy, true)
+            .await
+    }
+
+    /// Applies the global `--send-attempts` and `--confirm-timeout` overrides.
+    fn retry_policy(&self, policy: RetryPolicy) -> RetryPolicy {
+        policy.with_overrides(self.send_attempts, self.confirm_timeout)
+    }
+
+    async fn send_and_confirm_with(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        skip_confirm: bool,
+        policy: RetryPolicy,
+        mining: bool,
+    ) -> Result<Signature, Error> {
         debug!("Starting send_and_confirm with {} instructions", ixs.len());
 
         let progress_bar = spinner::new_progress_bar();
         let signer = self.signer();
         let client = self.rpc_client.clone();
+
+        // Make sure the fee payer can pay, which may switch fee payers
+        self.check_balance(&progress_bar, mining).await?;
         let fee_payer = self.fee_payer();
 
         debug!("Using signer: {}", signer.pubkey());
         debug!("Using fee payer: {}", fee_payer.pubkey());
         debug!("RPC client URL: {}", client.url());
 
-        // Return error, if balance is zero
-        self.check_balance().await;
-
         // Set compute budget
+        let lookup_tables = self.get_lookup_tables().await;
         let mut final_ixs = vec![];
         match compute_budget {
             ComputeBudget::Dynamic => {
                 debug!("Using dynamic compute budget");
-                todo!("simulate tx")
+                progress_bar.set_message("Simulating transaction...");
+                let cus = self
+                    .simulate_compute_units(ixs, &fee_payer.pubkey(), &lookup_tables, &progress_bar)
+                    .await?;
+                debug!("Simulated compute budget: {} CUs", cus);
+                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus))
             }
             ComputeBudget::Fixed(cus) => {
                 debug!("Using fixed compute budget: {} CUs", cus);
@@ -70,7 +135,8 @@ impl Miner {
         }
 
         // Set compute unit price
-        let priority_fee = self.priority_fee.unwrap_or(0);
+        let fee_controller = self.fee_controller.as_ref().filter(|_| mining);
+        let mut priority_fee = self.priority_fee.unwrap_or(0);
         debug!("Setting compute unit price: {} microlamports", priority_fee);
         final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
             priority_fee,
@@ -108,6 +174,9 @@ impl Miner {
             );
         }
 
+        // Programs called by each instruction, to attribute errors
+        let program_ids: Vec<Pubkey> = final_ixs.iter().map(|ix| ix.program_id).collect();
+
         // Build tx
         debug!("Building transaction with config: skip_preflight=true, commitment=Confirmed");
         let send_cfg = RpcSendTransactionConfig {
@@ -117,23 +186,89 @@ impl Miner {
             max_retries: Some(RPC_RETRIES),
             min_context_slot: None,
         };
-        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
+        let mut tx = VersionedTransaction::default();
 
         // Submit tx
+        let pubsub = match self.ws_url {
+            Some(ref ws_url) if !skip_confirm => match PubsubClient::new(ws_url).await {
+                Ok(pubsub) => Some(pubsub),
+                Err(err) => {
+                    warn!(
+                        "Failed to connect to {}: {}. Polling for confirmations",
+                        ws_url, err
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
         let mut attempts = 0;
+        let mut sends = 0;
+        let mut signed_at = Instant::now();
+        let confirm_retries =
+            (policy.confirm_timeout.as_millis() as u64 / CONFIRM_DELAY).max(1) as usize;
         loop {
+            // Give up once the policy's attempts are exhausted
+            if policy.max_attempts.is_some_and(|max| sends >= max) {
+                if let Some(controller) = fee_controller {
+                    controller.lock().unwrap().record(FeeOutcome {
+                        fee: priority_fee,
+                        landed: false,
+                        time_to_confirm: signed_at.elapsed(),
+                    });
+                }
+                let sig = tx.signatures.first().copied().unwrap_or_default();
+                error!("Transaction {} not confirmed after {} attempts", sig, sends);
+                log_error(&progress_bar, "Transaction timed out", true);
+                return Err(Error::TransactionTimeout(sig, sends));
+            }
+
+            // Back off before retrying
+            if sends > 0 {
+                let backoff = policy.backoff_for(sends);
+                if !backoff.is_zero() {
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+
             debug!("Transaction attempt #{}", attempts);
             progress_bar.set_message(format!("Submitting transaction... (attempt {})", attempts,));
 
             // Sign tx with a new blockhash (after approximately ~45 sec)
-            if attempts % 10 == 0 {
+            if attempts % policy.blockhash_refresh.max(1) == 0 {
                 debug!(
                     "Refreshing blockhash and recomputing fees (attempt {})",
                     attempts
                 );
 
                 // Reset the compute unit price
-                if self.dynamic_fee {
+                if let Some(controller) = fee_controller {
+                    let mut controller = controller.lock().unwrap();
+
+                    // The previous signing round expired without landing
+                    if attempts > 0 {
+                        controller.record(FeeOutcome {
+                            fee: priority_fee,
+                            landed: false,
+                            time_to_confirm: signed_at.elapsed(),
+                        });
+                    }
+                    priority_fee = controller.fee();
+                    debug!("Adaptive priority fee: {} microlamports", priority_fee);
+                    progress_bar.println(format!(
+                        "  Priority fee: {} microlamports (landing rate {})",
+                        priority_fee,
+                        controller
+                            .landing_rate()
+                            .map(|rate| format!("{:.0}%", rate * 100.0))
+                            .unwrap_or("n/a".to_string())
+                    ));
+                    final_ixs.remove(1);
+                    final_ixs.insert(
+                        1,
+                        ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+                    );
+                } else if self.fee_estimator.is_some() {
                     debug!("Computing dynamic priority fee");
                     let fee = match self.get_dynamic_priority_fee().await {
                         Ok(fee) => {
@@ -155,9 +290,9 @@ impl Miner {
                         }
                     };
 
+                    priority_fee = fee;
                     final_ixs.remove(1);
                     final_ixs.insert(1, ComputeBudgetInstruction::set_compute_unit_price(fee));
-                    tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
                 }
 
                 // Resign the tx
@@ -165,19 +300,39 @@ impl Miner {
                 let (hash, slot) = get_latest_blockhash_with_retries(&client).await?;
                 debug!("Got blockhash {} at slot {}", hash, slot);
 
-                if signer.pubkey() == fee_payer.pubkey() {
-                    debug!("Signing transaction with single signer");
-                    tx.sign(&[&signer], hash);
-                } else {
-                    debug!("Signing transaction with both signer and fee payer");
-                    tx.sign(&[&signer, &fee_payer], hash);
-                }
+                tx = sign_transaction(&final_ixs, &signer, &fee_payer, &lookup_tables, hash)?;
+                signed_at = Instant::now();
             }
 
+            // Subscribe before sending, so the notification can't be missed
+            let subscription = match pubsub {
+                Some(ref pubsub) => {
+                    let sub_cfg = RpcSignatureSubscribeConfig {
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        enable_received_notification: Some(false),
+                    };
+                    match pubsub
+                        .signature_subscribe(&tx.signatures[0], Some(sub_cfg))
+                        .await
+                    {
+                        Ok(subscription) => Some(subscription),
+                        Err(err) => {
+                            warn!("Signature subscription failed: {}", err);
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            // Broadcast to send-only endpoints
+            self.broadcast_transaction(&tx, send_cfg);
+
             // Send transaction
             attempts += 1;
+            sends += 1;
             debug!("Sending transaction to RPC");
-            match client.send_and_confirm_transaction(&tx).await {
+            match client.send_transaction_with_config(&tx, send_cfg).await {
                 Ok(sig) => {
                     debug!("Transaction sent successfully: {}", sig);
 
@@ -188,8 +343,50 @@ impl Miner {
                         return Ok(sig);
                     }
 
+                    // Wait for the signature notification, falling back to polling
+                    let mut confirm_retries = confirm_retries;
+                    if let Some((mut stream, unsubscribe)) = subscription {
+                        let notification =
+                            tokio::time::timeout(policy.confirm_timeout, stream.next()).await;
+                        drop(stream);
+                        unsubscribe().await;
+                        match notification {
+                            Ok(Some(response)) => {
+                                debug!("Signature notification: {:?}", response.value);
+                                if let RpcSignatureResult::ProcessedSignature(result) =
+                                    response.value
+                                {
+                                    if result.err.is_none() {
+                                        if let Some(controller) = fee_controller {
+                                            controller.lock().unwrap().record(FeeOutcome {
+                                                fee: priority_fee,
+                                                landed: true,
+                                                time_to_confirm: signed_at.elapsed(),
+                                            });
+                                        }
+                                        progress_bar.finish_with_message(format!(
+                                            "{} {}",
+                                            "OK".bold().green(),
+                                            sig
+                                        ));
+                                        return Ok(sig);
+                                    }
+                                }
+                                // Failed, let polling decode the error
+                                confirm_retries = 1;
+                            }
+                            Ok(None) => {
+                                warn!("Signature subscription closed");
+                            }
+                            Err(_) => {
+                                // Timed out, check once before resending
+                                confirm_retries = 1;
+                            }
+                        }
+                    }
+
                     // Confirm transaction
-                    'confirm: for confirm_attempt in 0..CONFIRM_RETRIES {
+                    'confirm: for confirm_attempt in 0..confirm_retries {
                         debug!(
                             "Confirmation attempt #{} for signature {}",
                             confirm_attempt, sig
@@ -202,52 +399,22 @@ impl Miner {
                                     if let Some(status) = status {
                                         if let Some(err) = status.err {
                                             debug!("Transaction error: {:?}", err);
-                                            match err {
-                                                // Instruction error
-                                                solana_sdk::transaction::TransactionError::InstructionError(_, err) => {
-                                                    match err {
-                                                        // Custom instruction error, parse into OreError
-                                                        solana_program::instruction::InstructionError::Custom(err_code) => {
-                                                            match err_code {
-                                                                e if e == OreError::NeedsReset as u32 => {
-                                                                    debug!("Needs reset error, retrying transaction");
-                                                                    attempts = 0;
-                                                                    log_error(&progress_bar, "Needs reset. Retrying...", false);
-                                                                    break 'confirm;
-                                                                },
-                                                                _ => {
-                                                                    error!("Custom instruction error: {}", err);
-                                                                    log_error(&progress_bar, &err.to_string(), true);
-                                                                    return Err(ClientError {
-                                                                        request: None,
-                                                                        kind: ClientErrorKind::Custom(err.to_string()),
-                                                                    });
-                                                                }
-                                                            }
-                                                        },
-
-                                                        // Non custom instruction error, return
-                                                        _ => {
-                                                            error!("Non-custom instruction error: {}", err);
-                                                            log_error(&progress_bar, &err.to_string(), true);
-                                                            return Err(ClientError {
-                                                                request: None,
-                                                                kind: ClientErrorKind::Custom(err.to_string()),
-                                                            });
-                                                        }
-                                                    }
-                                                },
-
-                                                // Non instruction error, return
-                                                _ => {
-                                                    error!("Non-instruction error: {}", err);
-                                                    log_error(&progress_bar, &err.to_string(), true);
-                                                    return Err(ClientError {
-                                                        request: None,
-                                                        kind: ClientErrorKind::Custom(err.to_string()),
-                                                    });
-                                                }
+                                            let err = decode_transaction_error(&err, &program_ids);
+                                            if err.is_retryable() {
+                                                // Re-sign and retry
+                                                debug!("Retryable error: {}", err);
+                                                attempts = 0;
+                                                let msg = if err.is_needs_reset() {
+                                                    "Needs reset. Retrying...".to_string()
+                                                } else {
+                                                    format!("{}. Retrying...", err)
+                                                };
+                                                log_error(&progress_bar, &msg, false);
+                                                break 'confirm;
                                             }
+                                            error!("Fatal transaction error: {}", err);
+                                            log_error(&progress_bar, &err.to_string(), true);
+                                            return Err(Error::Program(err));
                                         } else if let Some(confirmation) =
                                             status.confirmation_status
                                         {
@@ -264,6 +431,16 @@ impl Miner {
                                                 TransactionConfirmationStatus::Confirmed
                                                 | TransactionConfirmationStatus::Finalized => {
                                                     debug!("Transaction confirmed/finalized");
+                                                    if let Some(controller) = fee_controller {
+                                                        controller.lock().unwrap().record(
+                                                            FeeOutcome {
+                                                                fee: priority_fee,
+                                                                landed: true,
+                                                                time_to_confirm: signed_at
+                                                                    .elapsed(),
+                                                            },
+                                                        );
+                                                    }
                                                     progress_bar.finish_with_message(format!(
                                                         "{} {}",
                                                         "OK".bold().green(),
@@ -295,26 +472,233 @@ impl Miner {
         }
     }
 
-    pub async fn check_balance(&self) {
-        debug!("Checking balance for signer: {}", self.signer().pubkey());
-        let balance = self
-            .rpc_client
-            .get_balance(&self.signer().pubkey())
-            .await
-            .unwrap_or(0);
-        debug!("Current balance: {} ETH", lamports_to_sol(balance));
-        if balance < sol_to_lamports(MIN_ETH_BALANCE) {
-            error!(
-                "Insufficient balance: {} ETH < {} ETH",
-                lamports_to_sol(balance),
-                MIN_ETH_BALANCE
-            );
-            panic!(
-                "Insufficient balance: {} ETH < {} ETH",
-                lamports_to_sol(balance),
-                MIN_ETH_BALANCE
-            );
+    /// Sends a transaction that is already fully signed, e.g. one assembled by
+    /// `bitz tx submit`. It can't be re-signed, so it is resent as is until it
+    /// lands, fails or its blockhash expires. Durable nonce transactions don't
+    /// expire, and are resent until their nonce advances.
+    pub async fn send_and_confirm_signed(
+        &self,
+        tx: &Transaction,
+        skip_confirm: bool,
+    ) -> Result<Signature, Error> {
+        let progress_bar = spinner::new_progress_bar();
+        let nonce = nonce_account(tx);
+        let policy = self.retry_policy(match nonce {
+            Some(_) => RetryPolicy::persistent(),
+            None => RetryPolicy::default(),
+        });
+        let program_ids: Vec<Pubkey> = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ix| tx.message.account_keys[ix.program_id_index as usize])
+            .collect();
+        let send_cfg = RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: Some(RPC_RETRIES),
+            min_context_slot: None,
+        };
+        let sig = tx.signatures[0];
+        let confirm_retries =
+            (policy.confirm_timeout.as_millis() as u64 / CONFIRM_DELAY).max(1) as usize;
+        let mut sends = 0;
+        while policy.max_attempts.map_or(true, |max| sends < max) {
+            if sends > 0 {
+                tokio::time::sleep(policy.backoff_for(sends)).await;
+            }
+            progress_bar.set_message(format!("Submitting transaction... (attempt {})", sends));
+            self.broadcast_transaction(&VersionedTransaction::from(tx.clone()), send_cfg);
+            sends += 1;
+            if let Err(err) = self
+                .rpc_client
+                .send_transaction_with_config(tx, send_cfg)
+                .await
+            {
+                error!("Error submitting transaction: {}", err);
+                log_error(&progress_bar, &err.kind().to_string(), false);
+                continue;
+            }
+            if skip_confirm {
+                progress_bar.finish_with_message(format!("Sent: {}", sig));
+                return Ok(sig);
+            }
+
+            // Confirm transaction
+            for _ in 0..confirm_retries {
+                tokio::time::sleep(Duration::from_millis(CONFIRM_DELAY)).await;
+                let status = match self.rpc_client.get_signature_statuses(&[sig]).await {
+                    Ok(statuses) => statuses.value.into_iter().next().flatten(),
+                    Err(err) => {
+                        warn!("Error getting signature status: {}", err);
+                        log_error(&progress_bar, &err.kind().to_string(), false);
+                        continue;
+                    }
+                };
+                let Some(status) = status else {
+                    continue;
+                };
+                if let Some(err) = status.err {
+                    let err = decode_transaction_error(&err, &program_ids);
+                    error!("Transaction error: {}", err);
+                    log_error(&progress_bar, &err.to_string(), true);
+                    return Err(Error::Program(err));
+                }
+                if matches!(
+                    status.confirmation_status,
+                    Some(TransactionConfirmationStatus::Confirmed)
+                        | Some(TransactionConfirmationStatus::Finalized)
+                ) {
+                    progress_bar.finish_with_message(format!("{} {}", "OK".bold().green(), sig));
+                    return Ok(sig);
+                }
+            }
+
+            // Stop once the nonce advances or the blockhash expires, unless this
+            // transaction landed meanwhile
+            let expired = match nonce {
+                Some(nonce) => match get_nonce(&self.rpc_client, nonce).await {
+                    Ok(data) => (data.blockhash() != tx.message.recent_blockhash)
+                        .then(|| format!("nonce {} has advanced", nonce)),
+                    Err(_) => None,
+                },
+                None => match self
+                    .rpc_client
+                    .is_blockhash_valid(&tx.message.recent_blockhash, CommitmentConfig::confirmed())
+                    .await
+                {
+                    Ok(false) => Some(format!(
+                        "blockhash {} has expired",
+                        tx.message.recent_blockhash
+                    )),
+                    _ => None,
+                },
+            };
+            if let Some(reason) = expired {
+                if let Ok(Some(Ok(()))) = self.rpc_client.get_signature_status(&sig).await {
+                    progress_bar.finish_with_message(format!("{} {}", "OK".bold().green(), sig));
+                    return Ok(sig);
+                }
+                log_error(&progress_bar, &reason, true);
+                return Err(Error::Internal(format!(
+                    "{}, the transaction can no longer land",
+                    reason
+                )));
+            }
+        }
+        error!("Transaction {} not confirmed after {} attempts", sig, sends);
+        log_error(&progress_bar, "Transaction timed out", true);
+        Err(Error::TransactionTimeout(sig, sends))
+    }
+
+    /// Fires the signed transaction at every send-only endpoint without waiting.
+    /// Confirmation is left to the primary rpc client.
+    fn broadcast_transaction(&self, tx: &VersionedTransaction, send_cfg: RpcSendTransactionConfig) {
+        for client in self.send_rpc_clients.iter() {
+            let client = client.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match client.send_transaction_with_config(&tx, send_cfg).await {
+                    Ok(sig) => debug!("Broadcast {} via {}", sig, client.url()),
+                    Err(err) => warn!("Error broadcasting via {}: {}", client.url(), err),
+                }
+            });
+        }
+    }
+
+    /// Fetches the configured lookup tables. Tables that can't be fetched are
+    /// skipped, falling back to legacy transactions if none can.
+    async fn get_lookup_tables(&self) -> Vec<AddressLookupTableAccount> {
+        let mut lookup_tables = vec![];
+        for address in self.lookup_tables.iter() {
+            match get_lookup_table(&self.rpc_client, *address).await {
+                Ok(lookup_table) => lookup_tables.push(lookup_table),
+                Err(err) => warn!("Error fetching lookup table {}: {}", address, err),
+            }
         }
+        lookup_tables
+    }
+
+    /// Simulates the instructions, compiled as they will be sent, to size the
+    /// compute unit limit with a safety margin. Fails if the simulation hits an
+    /// error that resending can't fix, and falls back to the runtime maximum if
+    /// the rpc can't simulate.
+    pub(super) async fn simulate_compute_units(
+        &self,
+        ixs: &[Instruction],
+        fee_payer: &Pubkey,
+        lookup_tables: &[AddressLookupTableAccount],
+        progress_bar: &ProgressBar,
+    ) -> Result<u32, Error> {
+        let mut sim_ixs = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNITS),
+            ComputeBudgetInstruction::set_compute_unit_price(0),
+        ];
+        sim_ixs.extend_from_slice(ixs);
+        let program_ids: Vec<Pubkey> = sim_ixs.iter().map(|ix| ix.program_id).collect();
+        let message = compile_message(&sim_ixs, fee_payer, lookup_tables, Hash::default())?;
+        let tx = VersionedTransaction {
+            signatures: vec![
+                Signature::default();
+                message.header().num_required_signatures as usize
+            ],
+            message,
+        };
+        let sim_cfg = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(CommitmentConfig::confirmed()),
+            encoding: Some(UiTransactionEncoding::Base64),
+            ..Default::default()
+        };
+        let mut sim_err = None;
+        for attempt in 0..SIMULATION_RETRIES {
+            debug!("Simulation attempt #{}", attempt);
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(SIMULATION_DELAY)).await;
+            }
+            match self
+                .rpc_client
+                .simulate_transaction_with_config(&tx, sim_cfg.clone())
+                .await
+            {
+                Ok(sim) => {
+                    if let Some(err) = sim.value.err {
+                        let err = decode_transaction_error(&err, &program_ids);
+                        warn!("Simulation failed: {}", err);
+                        if !err.is_retryable() {
+                            log_error(progress_bar, &format!("Simulation failed: {}", err), true);
+                            return Err(Error::Program(err));
+                        }
+                        sim_err = Some(err);
+                        continue;
+                    }
+                    if let Some(units) = sim.value.units_consumed {
+                        let units = units + units * COMPUTE_UNIT_MARGIN / 100;
+                        return Ok(units.min(MAX_COMPUTE_UNITS as u64) as u32);
+                    }
+                    break;
+                }
+                Err(err) => {
+                    warn!("Error simulating transaction: {}", err);
+                }
+            }
+        }
+
+        // The transaction kept failing, so sending it would too
+        if let Some(err) = sim_err {
+            log_error(progress_bar, &format!("Simulation failed: {}", err), true);
+            return Err(Error::Program(err));
+        }
+        log_warning(
+            progress_bar,
+            &format!(
+                "Could not simulate transaction. Using {} CUs",
+                MAX_COMPUTE_UNITS
+            ),
+        );
+        Ok(MAX_COMPUTE_UNITS)
     }
 }
 
@@ -329,3 +713,42 @@ fn log_error(progress_bar: &ProgressBar, err: &str, finish: bool) {
 fn log_warning(progress_bar: &ProgressBar, msg: &str) {
     progress_bar.println(format!("  {} {}", "WARNING".bold().yellow(), msg));
 }
+
+/// Compiles the instructions as a v0 message if there are lookup tables to
+/// compress the accounts with, otherwise as a legacy message.
+fn compile_message(
+    ixs: &[Instruction],
+    fee_payer: &Pubkey,
+    lookup_tables: &[AddressLookupTableAccount],
+    hash: Hash,
+) -> Result<VersionedMessage, Error> {
+    if lookup_tables.is_empty() {
+        return Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixs,
+            Some(fee_payer),
+            &hash,
+        )));
+    }
+    let message = v0::Message::try_compile(fee_payer, ixs, lookup_tables, hash)
+        .map_err(|err| Error::Internal(format!("failed to compile transaction: {}", err)))?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// Compiles and signs the instructions, see `compile_message`.
+fn sign_transaction(
+    ixs: &[Instruction],
+    signer: &DynSigner,
+    fee_payer: &DynSigner,
+    lookup_tables: &[AddressLookupTableAccount],
+    hash: Hash,
+) -> Result<VersionedTransaction, Error> {
+    let message = compile_message(ixs, &fee_payer.pubkey(), lookup_tables, hash)?;
+    let tx = if signer.pubkey() == fee_payer.pubkey() {
+        debug!("Signing transaction with single signer");
+        VersionedTransaction::try_new(message, &[signer])
+    } else {
+        debug!("Signing transaction with both signer and fee payer");
+        VersionedTransaction::try_new(message, &[signer, fee_payer])
+    };
+    tx.map_err(|err| Error::Internal(format!("failed to sign transaction: {}", err)))
+}
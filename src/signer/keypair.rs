@@ -0,0 +1,65 @@
+use std::io::{BufRead, IsTerminal};
+
+use solana_sdk::signature::Keypair;
+
+use crate::{
+    error::Error,
+    utils::{check_key_permissions, read_passphrase, unlock_keystore, Keystore},
+};
+
+/// Reads a keypair file holding a base58 or JSON secret key, or an encrypted
+/// keystore, which is unlocked with its passphrase.
+pub fn read_keypair_from_file(filepath: &str) -> Result<Keypair, Error> {
+    let content = std::fs::read_to_string(filepath)
+        .map_err(|err| Error::Internal(format!("could not read keypair {}: {}", filepath, err)))?;
+
+    // Warn about key files other users can read
+    check_key_permissions(filepath);
+
+    // Unlock encrypted keystores
+    if let Some(keystore) = Keystore::parse(&content) {
+        return unlock_keystore(filepath, &keystore);
+    }
+    parse_keypair(&content)
+        .map_err(|err| Error::Internal(format!("invalid keypair {}: {}", filepath, err)))
+}
+
+/// Reads a base58 or JSON secret key from an environment variable.
+pub fn read_keypair_from_env(var: &str) -> Result<Keypair, Error> {
+    let content = std::env::var(var)
+        .map_err(|_| Error::Internal(format!("environment variable {} is not set", var)))?;
+    parse_keypair(&content)
+        .map_err(|err| Error::Internal(format!("invalid keypair in {}: {}", var, err)))
+}
+
+/// Reads a base58 or JSON secret key from stdin, prompting without echo if
+/// stdin is a terminal.
+pub fn read_keypair_from_stdin() -> Result<Keypair, Error> {
+    let content = if std::io::stdin().is_terminal() {
+        read_passphrase("Secret key (base58 or JSON): ")?
+    } else {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|err| Error::Internal(format!("failed to read stdin: {}", err)))?;
+        line
+    };
+    parse_keypair(&content)
+}
+
+/// Parses a secret key as a JSON byte array, like solana keypair files, or as
+/// a base58 string, like wallet exports.
+pub fn parse_keypair(content: &str) -> Result<Keypair, Error> {
+    let content = content.trim();
+    let bytes = if content.starts_with('[') {
+        serde_json::from_str::<Vec<u8>>(content)
+            .map_err(|err| Error::Internal(format!("invalid secret key: {}", err)))?
+    } else {
+        bs58::decode(content)
+            .into_vec()
+            .map_err(|err| Error::Internal(format!("invalid secret key: {}", err)))?
+    };
+    Keypair::from_bytes(&bytes)
+        .map_err(|err| Error::Internal(format!("invalid secret key: {}", err)))
+}
@@ -0,0 +1,9 @@
+mod keypair;
+mod pubkey;
+mod remote;
+mod source;
+
+pub use keypair::*;
+pub use pubkey::*;
+pub use remote::*;
+pub use source::*;
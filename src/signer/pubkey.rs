@@ -0,0 +1,51 @@
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Signature, Signer},
+    signer::SignerError,
+};
+
+/// A signer that only knows its public key, for building transactions on a host
+/// that doesn't hold the secret key. Signing fails, so the transaction has to
+/// be signed elsewhere, e.g. with `bitz tx sign` on an offline machine.
+pub struct PubkeySigner {
+    pubkey: Pubkey,
+}
+
+impl PubkeySigner {
+    pub fn new(pubkey: Pubkey) -> Self {
+        Self { pubkey }
+    }
+}
+
+impl Signer for PubkeySigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, _message: &[u8]) -> Result<Signature, SignerError> {
+        Err(SignerError::Custom(format!(
+            "no secret key for {}, sign the transaction where the key is held",
+            self.pubkey
+        )))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+    use crate::signer::load_signer;
+
+    #[test]
+    fn loads_pubkey_source_without_secret_key() {
+        let keypair = Keypair::new();
+        let signer = load_signer(&format!("pubkey:{}", keypair.pubkey()), None).unwrap();
+        assert_eq!(signer.pubkey(), keypair.pubkey());
+        assert!(signer.try_sign_message(b"message").is_err());
+    }
+}
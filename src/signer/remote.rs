@@ -0,0 +1,247 @@
+use std::{future::Future, net::SocketAddr, str::FromStr, time::Duration};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Signature, Signer},
+    signer::SignerError,
+};
+
+use crate::{error::Error, utils::http_client_builder};
+
+use super::DynSigner;
+
+/// Environment variable holding the bearer token shared with the remote signer.
+pub const REMOTE_SIGNER_TOKEN_ENV: &str = "BITZ_REMOTE_SIGNER_TOKEN";
+
+/// How long to wait for the remote signer to answer.
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+pub struct PubkeyResponse {
+    pub pubkey: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignRequest {
+    /// Base58 encoded message bytes.
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signature: String,
+}
+
+/// A signer whose key lives in a remote signing service, so that mining hosts
+/// never hold it. The service answers `GET /pubkey` with its public key and
+/// `POST /sign` with a signature over the posted message.
+pub struct RemoteSigner {
+    url: String,
+    pubkey: Pubkey,
+    token: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    /// Connects to the signing service, through the proxy if any, and fetches
+    /// the public key it signs for.
+    pub fn connect(url: &str, proxy: Option<&str>) -> Result<Self, Error> {
+        let http_client = http_client_builder(proxy)?
+            .timeout(REMOTE_SIGNER_TIMEOUT)
+            .build()?;
+        let mut signer = Self {
+            url: url.trim_end_matches('/').to_string(),
+            pubkey: Pubkey::default(),
+            token: std::env::var(REMOTE_SIGNER_TOKEN_ENV).ok(),
+            http_client,
+        };
+        let response: PubkeyResponse = block_on(async {
+            signer
+                .request(signer.http_client.get(format!("{}/pubkey", signer.url)))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+        })
+        .map_err(|err| {
+            Error::Internal(format!("remote signer {} unavailable: {}", signer.url, err))
+        })?;
+        signer.pubkey = Pubkey::from_str(&response.pubkey)?;
+        Ok(signer)
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.token {
+            Some(ref token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<SignResponse, reqwest::Error> {
+        let request = SignRequest {
+            message: bs58::encode(message).into_string(),
+        };
+        self.request(self.http_client.post(format!("{}/sign", self.url)))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let response =
+            block_on(self.sign(message)).map_err(|err| SignerError::Connection(err.to_string()))?;
+        let signature = Signature::from_str(&response.signature)
+            .map_err(|err| SignerError::Custom(format!("invalid remote signature: {}", err)))?;
+
+        // Never pass on a signature the key didn't make
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Custom(format!(
+                "remote signer {} returned a signature not made by {}",
+                self.url, self.pubkey
+            )));
+        }
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Runs a future to completion from synchronous code, which the `Signer`
+/// trait requires, whether or not it is called from within the runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build runtime")
+            .block_on(future),
+    }
+}
+
+/// Serves a signer over the remote signer protocol. Meant as a local stand-in
+/// for a real signing service: it signs any message it is sent, so it must
+/// only listen where the miners it signs for can reach it.
+pub async fn serve_signer(signer: DynSigner, listen: &str) -> Result<(), Error> {
+    let addr = SocketAddr::from_str(listen)
+        .map_err(|err| Error::Internal(format!("Invalid listen address: {}", err)))?;
+    let app = Router::new()
+        .route("/pubkey", get(pubkey))
+        .route("/sign", post(sign))
+        .with_state(signer.clone());
+    println!(
+        "{} Signer {} listening on http://{}",
+        "INFO".bold().green(),
+        signer.pubkey(),
+        addr
+    );
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|err| Error::Internal(format!("Failed to bind {}: {}", addr, err)))?;
+    axum::serve(listener, app)
+        .await
+        .map_err(|err| Error::Internal(err.to_string()))
+}
+
+/// Checks the bearer token, if the server was started with one.
+fn authorize(headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Ok(token) = std::env::var(REMOTE_SIGNER_TOKEN_ENV) else {
+        return Ok(());
+    };
+    let expected = format!("Bearer {}", token);
+    match headers.get(axum::http::header::AUTHORIZATION) {
+        Some(value) if value.as_bytes() == expected.as_bytes() => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+async fn pubkey(
+    State(signer): State<DynSigner>,
+    headers: HeaderMap,
+) -> Result<Json<PubkeyResponse>, StatusCode> {
+    authorize(&headers)?;
+    Ok(Json(PubkeyResponse {
+        pubkey: signer.pubkey().to_string(),
+    }))
+}
+
+async fn sign(
+    State(signer): State<DynSigner>,
+    headers: HeaderMap,
+    Json(request): Json<SignRequest>,
+) -> Result<Json<SignResponse>, StatusCode> {
+    authorize(&headers)?;
+    let message = bs58::decode(&request.message)
+        .into_vec()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let signature = signer
+        .try_sign_message(&message)
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    Ok(Json(SignResponse {
+        signature: signature.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn signs_through_serve_signer() {
+        let keypair = Arc::new(Keypair::new());
+        let pubkey = keypair.pubkey();
+
+        // Serve on a free local port
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let listen = format!("127.0.0.1:{}", port);
+        tokio::spawn({
+            let listen = listen.clone();
+            async move { serve_signer(keypair, &listen).await }
+        });
+
+        // Wait for the server to come up
+        let url = format!("http://{}", listen);
+        let mut signer = RemoteSigner::connect(&url, None);
+        for _ in 0..50 {
+            if signer.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            signer = RemoteSigner::connect(&url, None);
+        }
+        let signer = signer.unwrap();
+        assert_eq!(signer.pubkey(), pubkey);
+
+        let message = b"bitz remote signer";
+        let signature = signer.try_sign_message(message).unwrap();
+        assert!(signature.verify(pubkey.as_ref(), message));
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+use crate::error::Error;
+
+use super::{
+    read_keypair_from_env, read_keypair_from_file, read_keypair_from_stdin, PubkeySigner,
+    RemoteSigner,
+};
+
+/// A loaded signer, shared by everything that signs on its behalf.
+pub type DynSigner = Arc<dyn Signer + Send + Sync>;
+
+/// Where a signer comes from, as given to `--keypair` and friends.
+pub enum SignerSource {
+    /// A keypair file: base58, JSON or an encrypted keystore.
+    File(String),
+    /// An environment variable holding a base58 or JSON secret key.
+    Env(String),
+    /// A secret key read from stdin, hidden when it is a terminal.
+    Prompt,
+    /// A remote signing service, which holds the key on another host.
+    Remote(String),
+    /// A bare public key, for building transactions that are signed elsewhere.
+    Pubkey(String),
+}
+
+impl SignerSource {
+    pub fn parse(source: &str) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            SignerSource::Remote(source.to_string())
+        } else if let Some(var) = source.strip_prefix("env:") {
+            SignerSource::Env(var.to_string())
+        } else if let Some(pubkey) = source.strip_prefix("pubkey:") {
+            SignerSource::Pubkey(pubkey.to_string())
+        } else if source == "prompt:" || source == "stdin:" {
+            SignerSource::Prompt
+        } else {
+            let path = source.strip_prefix("file:").unwrap_or(source);
+            SignerSource::File(path.to_string())
+        }
+    }
+}
+
+/// Loads the signer for a source, reaching remote signers through the proxy if
+/// any. Each source is loaded once per process, so a keypair that also pays
+/// fees is only prompted for or fetched once.
+pub fn load_signer(source: &str, proxy: Option<&str>) -> Result<DynSigner, Error> {
+    static LOADED: OnceLock<Mutex<HashMap<String, DynSigner>>> = OnceLock::new();
+    let loaded = LOADED.get_or_init(Default::default);
+    if let Some(signer) = loaded.lock().unwrap().get(source) {
+        return Ok(signer.clone());
+    }
+    let signer: DynSigner = match SignerSource::parse(source) {
+        SignerSource::File(path) => Arc::new(read_keypair_from_file(&path)?),
+        SignerSource::Env(var) => Arc::new(read_keypair_from_env(&var)?),
+        SignerSource::Prompt => Arc::new(read_keypair_from_stdin()?),
+        SignerSource::Remote(url) => Arc::new(RemoteSigner::connect(&url, proxy)?),
+        SignerSource::Pubkey(pubkey) => Arc::new(PubkeySigner::new(Pubkey::from_str(&pubkey)?)),
+    };
+    loaded
+        .lock()
+        .unwrap()
+        .insert(source.to_string(), signer.clone());
+    Ok(signer)
+}
@@ -0,0 +1,289 @@
+use std::{fs, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command};
+use toml::{Table, Value};
+
+use crate::error::Error;
+
+/// Environment variable overriding the location of the config file.
+pub const CONFIG_FILE_ENV: &str = "BITZ_CONFIG_FILE";
+/// Environment variable selecting the profile, like `--profile`.
+pub const PROFILE_ENV: &str = "BITZ_PROFILE";
+/// Prefix of the environment variables overriding individual options.
+const ENV_PREFIX: &str = "BITZ";
+
+/// The bitz config file, a TOML table of option defaults.
+///
+/// Top level keys are global options and tables named after commands hold
+/// defaults for their options, both keyed by long flag name. Named profiles
+/// under `[profile.<name>]` have the same layout and override the top level:
+///
+/// ```toml
+/// rpc = "https://mainnetbeta-rpc.eclipse.xyz/"
+///
+/// [collect]
+/// cores = "8"
+///
+/// [profile.rig-a.collect]
+/// cores = "ALL"
+/// ```
+pub struct BitzConfig {
+    pub path: PathBuf,
+    table: Table,
+}
+
+impl BitzConfig {
+    /// Loads the config file, which may not exist yet.
+    pub fn load() -> Result<Self, Error> {
+        let path = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => default_config_path(),
+        };
+        let table = match fs::read_to_string(&path) {
+            Ok(data) => data.parse::<Table>().map_err(|err| {
+                Error::Internal(format!("failed to parse {}: {}", path.display(), err))
+            })?,
+            Err(_) => Table::new(),
+        };
+        Ok(Self { path, table })
+    }
+
+    /// The options in effect for a profile, the top level merged with the
+    /// profile's overrides.
+    pub fn resolve(&self, profile: Option<&str>) -> Result<Table, Error> {
+        let mut resolved = self.table.clone();
+        let profiles = resolved.remove("profile");
+        if let Some(profile) = profile {
+            let overrides = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(profile))
+                .and_then(Value::as_table)
+                .ok_or_else(|| Error::Internal(format!("profile {} not found", profile)))?;
+            merge(&mut resolved, overrides);
+        }
+        Ok(resolved)
+    }
+
+    /// The resolved value of a dotted key, e.g. `collect.cores`.
+    pub fn get(&self, key: &str, profile: Option<&str>) -> Result<Option<Value>, Error> {
+        let resolved = self.resolve(profile)?;
+        let mut value = None;
+        let mut table = Some(&resolved);
+        for part in key.split('.') {
+            value = table.and_then(|table| table.get(part));
+            table = value.and_then(Value::as_table);
+        }
+        Ok(value.cloned())
+    }
+
+    /// Sets a dotted key, in the profile if given, and saves the file.
+    pub fn set(&mut self, key: &str, value: &str, profile: Option<&str>) -> Result<(), Error> {
+        let mut parts: Vec<&str> = key.split('.').collect();
+        if let Some(profile) = profile {
+            parts.splice(0..0, ["profile", profile]);
+        }
+        let (last, parents) = parts.split_last().expect("key has at least one part");
+        let mut table = &mut self.table;
+        for part in parents {
+            table = table
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| Error::Internal(format!("{} is not a table", part)))?;
+        }
+        table.insert(last.to_string(), parse_value(value));
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                Error::Internal(format!("failed to create {}: {}", parent.display(), err))
+            })?;
+        }
+        let data =
+            toml::to_string_pretty(&self.table).map_err(|err| Error::Internal(err.to_string()))?;
+        fs::write(&self.path, data).map_err(|err| {
+            Error::Internal(format!("failed to write {}: {}", self.path.display(), err))
+        })
+    }
+}
+
+/// The profile selected with `--profile` or its environment variable. Read
+/// ahead of argument parsing, since the profile decides the defaults.
+pub fn selected_profile() -> Option<String> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            return args.next();
+        }
+        if let Some(profile) = arg.strip_prefix("--profile=") {
+            return Some(profile.to_string());
+        }
+    }
+    std::env::var(PROFILE_ENV).ok()
+}
+
+fn default_config_path() -> PathBuf {
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".config"),
+    };
+    config_dir.join("bitz").join("config.toml")
+}
+
+/// Merges `overrides` into `table`, recursing into tables present in both.
+fn merge(table: &mut Table, overrides: &Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(key), value) {
+            (Some(Value::Table(table)), Value::Table(overrides)) => merge(table, overrides),
+            _ => {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Parses a value given on the command line, keeping numbers and booleans typed.
+fn parse_value(value: &str) -> Value {
+    if let Ok(value) = value.parse::<i64>() {
+        Value::Integer(value)
+    } else if let Ok(value) = value.parse::<f64>() {
+        Value::Float(value)
+    } else if let Ok(value) = value.parse::<bool>() {
+        Value::Boolean(value)
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+/// Whether a dotted key names an option of the command or its subcommands.
+pub fn is_option(cmd: &Command, key: &str) -> bool {
+    let mut cmd = cmd;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return cmd.get_arguments().any(|arg| arg.get_long() == Some(part));
+        }
+        match cmd.find_subcommand(part) {
+            Some(subcommand) => cmd = subcommand,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Makes every option of the command fall back to its environment variable,
+/// then to the config file, before its built-in default. Command line flags
+/// still take precedence over both.
+pub fn apply_config(cmd: Command, values: &Table) -> Command {
+    apply_config_with_prefix(cmd, values, ENV_PREFIX)
+}
+
+fn apply_config_with_prefix(mut cmd: Command, values: &Table, prefix: &str) -> Command {
+    let options: Vec<(String, String)> = cmd
+        .get_arguments()
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_long()?.to_string())))
+        .filter(|(_, long)| long != "help" && long != "version")
+        .collect();
+    for (id, long) in options {
+        let env = format!("{}_{}", prefix, long.to_uppercase().replace('-', "_"));
+        let value = values.get(&long).cloned();
+        cmd = cmd.mut_arg(id.as_str(), |arg| with_default(arg.env(env), value));
+    }
+    let subcommands: Vec<String> = cmd
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    for name in subcommands {
+        let values = values
+            .get(&name)
+            .and_then(Value::as_table)
+            .cloned()
+            .unwrap_or_default();
+        let prefix = format!("{}_{}", prefix, name.to_uppercase().replace('-', "_"));
+        cmd = cmd.mut_subcommand(name.as_str(), |subcommand| {
+            apply_config_with_prefix(subcommand, &values, &prefix)
+        });
+    }
+    cmd
+}
+
+fn with_default(arg: Arg, value: Option<Value>) -> Arg {
+    let Some(value) = value else {
+        return arg;
+    };
+    match value {
+        Value::Array(values) if matches!(arg.get_action(), ArgAction::Append) => {
+            arg.default_values(values.iter().map(value_to_string).collect::<Vec<_>>())
+        }
+        value => arg.default_value(value_to_string(&value)),
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(data: &str) -> BitzConfig {
+        BitzConfig {
+            path: PathBuf::new(),
+            table: data.parse().unwrap(),
+        }
+    }
+
+    const CONFIG: &str = r#"
+rpc = "https://rpc.example"
+priority-fee = 100
+
+[collect]
+cores = "8"
+buffer-time = 5
+
+[profile.rig-a]
+priority-fee = 500
+
+[profile.rig-a.collect]
+cores = "ALL"
+"#;
+
+    #[test]
+    fn profiles_override_the_top_level() {
+        let config = config(CONFIG);
+        let resolved = config.resolve(Some("rig-a")).unwrap();
+        assert_eq!(resolved["rpc"].as_str(), Some("https://rpc.example"));
+        assert_eq!(resolved["priority-fee"].as_integer(), Some(500));
+
+        // Command tables merge key by key
+        let collect = resolved["collect"].as_table().unwrap();
+        assert_eq!(collect["cores"].as_str(), Some("ALL"));
+        assert_eq!(collect["buffer-time"].as_integer(), Some(5));
+        assert!(!resolved.contains_key("profile"));
+    }
+
+    #[test]
+    fn no_profile_uses_the_top_level() {
+        let config = config(CONFIG);
+        assert_eq!(
+            config.get("collect.cores", None).unwrap(),
+            Some(Value::String("8".to_string()))
+        );
+        assert_eq!(
+            config.get("priority-fee", None).unwrap(),
+            Some(Value::Integer(100))
+        );
+        assert_eq!(config.get("collect.missing", None).unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_profiles_are_an_error() {
+        assert!(config(CONFIG).resolve(Some("rig-b")).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client::{http_sender::HttpSender, rpc_client::RpcClientConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use url::Url;
+
+use crate::error::Error;
+
+/// Environment variables consulted, in order, when no `--proxy` is given.
+const PROXY_ENV_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+
+/// Timeout solana's own http sender uses for rpc requests.
+const RPC_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Resolves the proxy for outbound traffic from the `--proxy` flag, falling back
+/// to the standard proxy environment variables. Supports http(s) and socks5 urls.
+pub fn resolve_proxy(proxy: Option<String>) -> Option<String> {
+    proxy
+        .or_else(|| {
+            PROXY_ENV_VARS
+                .iter()
+                .find_map(|var| std::env::var(var).ok())
+        })
+        .filter(|proxy| !proxy.is_empty())
+}
+
+/// Derives the PubSub endpoint from an rpc url, following the solana convention
+/// of swapping the scheme and, for explicit ports, using the next port.
+pub fn websocket_url(rpc_url: &str) -> Option<String> {
+    let mut url = Url::parse(rpc_url).ok()?;
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        "http" => "ws",
+        _ => return None,
+    };
+    url.set_scheme(scheme).ok()?;
+    if let Some(port) = url.port() {
+        url.set_port(Some(port.checked_add(1)?)).ok()?;
+    }
+    Some(url.to_string())
+}
+
+/// Starts a client builder routed through the proxy, if any.
+pub fn http_client_builder(proxy: Option<&str>) -> Result<reqwest::ClientBuilder, Error> {
+    let builder = reqwest::Client::builder();
+    let Some(proxy) = proxy else {
+        return Ok(builder);
+    };
+    let proxy = reqwest::Proxy::all(proxy)
+        .map_err(|err| Error::Internal(format!("Invalid proxy {}: {}", proxy, err)))?;
+    Ok(builder.proxy(proxy))
+}
+
+/// Builds the rpc client, routed through the proxy, if any. The solana sender
+/// is built on an older reqwest, so its client is configured separately.
+pub fn new_rpc_client(
+    url: String,
+    commitment: CommitmentConfig,
+    proxy: Option<&str>,
+) -> Result<RpcClient, Error> {
+    let Some(proxy) = proxy else {
+        return Ok(RpcClient::new_with_commitment(url, commitment));
+    };
+    let proxy = reqwest_011::Proxy::all(proxy)
+        .map_err(|err| Error::Internal(format!("Invalid proxy {}: {}", proxy, err)))?;
+    let client = reqwest_011::Client::builder()
+        .proxy(proxy)
+        .timeout(RPC_REQUEST_TIMEOUT)
+        .build()
+        .map_err(|err| Error::Internal(err.to_string()))?;
+    let sender = HttpSender::new_with_client(url, client);
+    Ok(RpcClient::new_sender(
+        sender,
+        RpcClientConfig::with_commitment(commitment),
+    ))
+}
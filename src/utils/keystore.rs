@@ -0,0 +1,257 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use colored::*;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::error::Error;
+
+/// Environment variable holding the keystore passphrase, for unattended hosts.
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "BITZ_KEYSTORE_PASSPHRASE";
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// A keypair encrypted with a passphrase. The passphrase is stretched with
+/// argon2id and the secret key sealed with XChaCha20-Poly1305.
+#[derive(Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u8,
+    pub pubkey: String,
+    pub kdf: KdfParams,
+    /// Base58 encoded.
+    pub nonce: String,
+    /// Base58 encoded encrypted secret key.
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Base58 encoded.
+    pub salt: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Keystore {
+    pub fn encrypt(keypair: &Keypair, passphrase: &str) -> Result<Self, Error> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let kdf = KdfParams {
+            salt: bs58::encode(salt).into_string(),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        };
+        let cipher = kdf.cipher(passphrase)?;
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), keypair.to_bytes().as_ref())
+            .map_err(|_| Error::Internal("failed to encrypt keypair".to_string()))?;
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            pubkey: keypair.pubkey().to_string(),
+            kdf,
+            nonce: bs58::encode(nonce).into_string(),
+            ciphertext: bs58::encode(ciphertext).into_string(),
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<Keypair, Error> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(Error::Internal(format!(
+                "unsupported keystore version {}",
+                self.version
+            )));
+        }
+        let nonce = decode(&self.nonce, "nonce")?;
+        let ciphertext = decode(&self.ciphertext, "ciphertext")?;
+        if nonce.len() != NONCE_LEN {
+            return Err(Error::Internal("invalid keystore nonce".to_string()));
+        }
+        let cipher = self.kdf.cipher(passphrase)?;
+        let secret = cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| Error::Internal("wrong passphrase".to_string()))?;
+        Keypair::from_bytes(&secret)
+            .map_err(|err| Error::Internal(format!("invalid keystore secret: {}", err)))
+    }
+
+    /// Parses a keystore, or returns `None` if the data is not one.
+    pub fn parse(data: &str) -> Option<Self> {
+        serde_json::from_str(data).ok()
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Error> {
+        let data =
+            serde_json::to_string_pretty(self).map_err(|err| Error::Internal(err.to_string()))?;
+        write_secret_file(path, data.as_bytes())
+    }
+}
+
+impl KdfParams {
+    fn cipher(&self, passphrase: &str) -> Result<XChaCha20Poly1305, Error> {
+        let salt = decode(&self.salt, "salt")?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|err| Error::Internal(format!("invalid keystore parameters: {}", err)))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|err| Error::Internal(format!("failed to derive key: {}", err)))?;
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+fn decode(value: &str, field: &str) -> Result<Vec<u8>, Error> {
+    bs58::decode(value)
+        .into_vec()
+        .map_err(|err| Error::Internal(format!("invalid keystore {}: {}", field, err)))
+}
+
+/// Unlocks a keystore with the passphrase from the environment, or prompts
+/// for it. Each keystore is unlocked once per process.
+pub fn unlock_keystore(path: &str, keystore: &Keystore) -> Result<Keypair, Error> {
+    static UNLOCKED: OnceLock<Mutex<HashMap<String, [u8; 64]>>> = OnceLock::new();
+    let unlocked = UNLOCKED.get_or_init(Default::default);
+    if let Some(secret) = unlocked.lock().unwrap().get(path) {
+        return Keypair::from_bytes(secret).map_err(|err| Error::Internal(err.to_string()));
+    }
+    let passphrase = match std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => read_passphrase(&format!("Passphrase for {} ({}): ", path, keystore.pubkey))?,
+    };
+    let keypair = keystore.decrypt(&passphrase)?;
+    unlocked
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), keypair.to_bytes());
+    Ok(keypair)
+}
+
+pub fn read_passphrase(prompt: &str) -> Result<String, Error> {
+    rpassword::prompt_password(prompt)
+        .map_err(|err| Error::Internal(format!("failed to read passphrase: {}", err)))
+}
+
+/// Asks for a new passphrase twice, unless given in the environment.
+pub fn read_new_passphrase() -> Result<String, Error> {
+    if let Ok(passphrase) = std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(Error::Internal("passphrase must not be empty".to_string()));
+    }
+    if read_passphrase("Repeat passphrase: ")? != passphrase {
+        return Err(Error::Internal("passphrases do not match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+/// Warns loudly, once per file, if a key file can be read by other users.
+pub fn check_key_permissions(path: &str) {
+    static WARNED: OnceLock<Mutex<Vec<String>>> = OnceLock::new();
+    let Some(mode) = file_mode(Path::new(path)) else {
+        return;
+    };
+    if mode & 0o077 == 0 {
+        return;
+    }
+    let mut warned = WARNED.get_or_init(Default::default).lock().unwrap();
+    if warned.iter().any(|warned| warned == path) {
+        return;
+    }
+    warned.push(path.to_string());
+    eprintln!(
+        "{} Key file {} is readable by {} (mode {:o}). Restrict it with `chmod 600 {}`, or encrypt it with `bitz key encrypt {}`.",
+        "WARNING".bold().yellow(),
+        path,
+        if mode & 0o007 != 0 { "all users" } else { "its group" },
+        mode & 0o777,
+        path,
+        path,
+    );
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Writes a file only its owner can read.
+pub fn write_secret_file(path: &str, data: &[u8]) -> Result<(), Error> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let write = |options: &fs::OpenOptions| -> std::io::Result<()> {
+        use std::io::Write;
+        let mut file = options.open(path)?;
+        file.write_all(data)?;
+
+        // The mode only applies to new files
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    };
+    write(&options).map_err(|err| Error::Internal(format!("failed to write {}: {}", path, err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let keypair = Keypair::new();
+        let keystore = Keystore::encrypt(&keypair, "correct horse").unwrap();
+        assert_eq!(keystore.pubkey, keypair.pubkey().to_string());
+
+        // Survives serialization
+        let data = serde_json::to_string(&keystore).unwrap();
+        let keystore = Keystore::parse(&data).unwrap();
+        let decrypted = keystore.decrypt("correct horse").unwrap();
+        assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_passphrase() {
+        let keystore = Keystore::encrypt(&Keypair::new(), "correct horse").unwrap();
+        assert!(keystore.decrypt("battery staple").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_plain_keypairs() {
+        let keypair = Keypair::new();
+        assert!(Keystore::parse(&format!("{:?}", keypair.to_bytes().to_vec())).is_none());
+        assert!(Keystore::parse(&keypair.to_base58_string()).is_none());
+    }
+}
@@ -1,8 +1,16 @@
+mod config;
+mod http;
 mod io;
+mod keystore;
+mod program_error;
 mod rpc;
 mod table;
 
+pub use config::*;
+pub use http::*;
 pub use io::*;
+pub use keystore::*;
+pub use program_error::*;
 pub use rpc::*;
 pub use table::*;
 
@@ -0,0 +1,354 @@
+use std::fmt;
+
+use eore_api::error::OreError;
+use eore_boost_api::error::BoostError;
+use ore_pool_api::error::PoolError;
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_sdk::transaction::TransactionError;
+
+/// Whether resending the transaction may succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    Retryable,
+    Fatal,
+}
+
+/// A transaction error decoded against the programs it called.
+#[derive(Clone, Debug)]
+pub struct DecodedError {
+    /// Index of the failing instruction, if the error came from one.
+    pub instruction: Option<u8>,
+    pub program: &'static str,
+    pub code: Option<u32>,
+    pub name: String,
+    pub explanation: String,
+    pub class: ErrorClass,
+}
+
+impl DecodedError {
+    pub fn is_retryable(&self) -> bool {
+        self.class == ErrorClass::Retryable
+    }
+
+    /// Whether this is the ORE program asking for a reset before mining.
+    pub fn is_needs_reset(&self) -> bool {
+        self.program == "ORE" && self.code == Some(OreError::NeedsReset as u32)
+    }
+}
+
+impl fmt::Display for DecodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(instruction) = self.instruction {
+            write!(f, "instruction #{} ", instruction)?;
+        }
+        write!(f, "{} {}: {}", self.program, self.name, self.explanation)
+    }
+}
+
+struct KnownError {
+    code: u32,
+    name: &'static str,
+    explanation: &'static str,
+    class: ErrorClass,
+}
+
+/// Custom errors of the ORE program, by code.
+const ORE_ERRORS: &[KnownError] = &[
+    KnownError {
+        code: OreError::NeedsReset as u32,
+        name: "NeedsReset",
+        explanation: "the epoch has ended and the program needs a reset",
+        class: ErrorClass::Retryable,
+    },
+    KnownError {
+        code: OreError::HashInvalid as u32,
+        name: "HashInvalid",
+        explanation: "the submitted solution does not hash to the challenge",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: OreError::HashTooEasy as u32,
+        name: "HashTooEasy",
+        explanation: "the solution is below the minimum difficulty",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: OreError::ClaimTooLarge as u32,
+        name: "ClaimTooLarge",
+        explanation: "the claim exceeds the claimable balance",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: OreError::ClockInvalid as u32,
+        name: "ClockInvalid",
+        explanation: "the cluster clock is behind the proof, try again shortly",
+        class: ErrorClass::Retryable,
+    },
+    KnownError {
+        code: OreError::Spam as u32,
+        name: "Spam",
+        explanation: "the solution was submitted too soon after the last one",
+        class: ErrorClass::Retryable,
+    },
+    KnownError {
+        code: OreError::MaxSupply as u32,
+        name: "MaxSupply",
+        explanation: "the maximum token supply has been reached",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: OreError::AuthFailed as u32,
+        name: "AuthFailed",
+        explanation: "the proof does not belong to the signer",
+        class: ErrorClass::Fatal,
+    },
+];
+
+/// Custom errors of the pool program, by code.
+const POOL_ERRORS: &[KnownError] = &[
+    KnownError {
+        code: PoolError::MissingMiningReward as u32,
+        name: "MissingMiningReward",
+        explanation: "the mine instruction returned no reward",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: PoolError::CouldNotParseMiningReward as u32,
+        name: "CouldNotParseMiningReward",
+        explanation: "the mine instruction returned a reward the pool could not parse",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: PoolError::WithdrawOnlyMode as u32,
+        name: "WithdrawOnlyMode",
+        explanation: "pool staking only accepts withdrawals",
+        class: ErrorClass::Fatal,
+    },
+    KnownError {
+        code: PoolError::AttributionTooLarge as u32,
+        name: "AttributionTooLarge",
+        explanation:
+            "the attribution exceeds the pool's claimable rewards, try again once rewards land",
+        class: ErrorClass::Retryable,
+    },
+];
+
+/// Custom errors of the boost program, by code.
+const BOOST_ERRORS: &[KnownError] = &[KnownError {
+    code: BoostError::Dummy as u32,
+    name: "Dummy",
+    explanation: "the boost program rejected the instruction",
+    class: ErrorClass::Fatal,
+}];
+
+/// The custom errors a program is known to return.
+fn known_errors(program_id: &Pubkey) -> &'static [KnownError] {
+    if *program_id == eore_api::ID {
+        ORE_ERRORS
+    } else if *program_id == ore_pool_api::ID {
+        POOL_ERRORS
+    } else if *program_id == eore_boost_api::ID {
+        BOOST_ERRORS
+    } else {
+        &[]
+    }
+}
+
+/// Names the program an instruction called.
+fn program_name(program_id: &Pubkey) -> &'static str {
+    if *program_id == eore_api::ID {
+        "ORE"
+    } else if *program_id == eore_boost_api::ID {
+        "Boost"
+    } else if *program_id == ore_pool_api::ID {
+        "Pool"
+    } else if *program_id == spl_token::ID {
+        "Token"
+    } else if *program_id == spl_associated_token_account::ID {
+        "Associated Token"
+    } else if *program_id == solana_program::system_program::ID {
+        "System"
+    } else if *program_id == solana_sdk::compute_budget::ID {
+        "Compute Budget"
+    } else {
+        "Program"
+    }
+}
+
+/// Decodes a transaction error. `program_ids` are the programs called by each
+/// instruction, in order, so instruction errors can be attributed.
+pub fn decode_transaction_error(err: &TransactionError, program_ids: &[Pubkey]) -> DecodedError {
+    match err {
+        TransactionError::InstructionError(index, err) => {
+            decode_instruction_error(*index, program_ids.get(*index as usize), err)
+        }
+        err => {
+            let class = match err {
+                TransactionError::BlockhashNotFound
+                | TransactionError::AccountInUse
+                | TransactionError::WouldExceedMaxBlockCostLimit
+                | TransactionError::WouldExceedMaxAccountCostLimit
+                | TransactionError::WouldExceedMaxVoteCostLimit
+                | TransactionError::WouldExceedAccountDataBlockLimit
+                | TransactionError::TooManyAccountLocks
+                | TransactionError::ClusterMaintenance => ErrorClass::Retryable,
+                _ => ErrorClass::Fatal,
+            };
+            DecodedError {
+                instruction: None,
+                program: "Transaction",
+                code: None,
+                name: format!("{:?}", err),
+                explanation: err.to_string(),
+                class,
+            }
+        }
+    }
+}
+
+fn decode_instruction_error(
+    index: u8,
+    program_id: Option<&Pubkey>,
+    err: &InstructionError,
+) -> DecodedError {
+    let program = program_id.map(program_name).unwrap_or("Program");
+    let InstructionError::Custom(code) = err else {
+        return DecodedError {
+            instruction: Some(index),
+            program,
+            code: None,
+            name: format!("{:?}", err),
+            explanation: err.to_string(),
+            class: ErrorClass::Fatal,
+        };
+    };
+    let known = program_id
+        .map(known_errors)
+        .unwrap_or_default()
+        .iter()
+        .find(|known| known.code == *code);
+    match known {
+        Some(known) => DecodedError {
+            instruction: Some(index),
+            program,
+            code: Some(*code),
+            name: known.name.to_string(),
+            explanation: known.explanation.to_string(),
+            class: known.class,
+        },
+        None => DecodedError {
+            instruction: Some(index),
+            program,
+            code: Some(*code),
+            name: format!("Error {:#x}", code),
+            explanation: "unrecognized custom program error".to_string(),
+            class: ErrorClass::Fatal,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM_IDS: [Pubkey; 3] = [
+        solana_sdk::compute_budget::ID,
+        solana_sdk::compute_budget::ID,
+        eore_api::ID,
+    ];
+
+    #[test]
+    fn decodes_known_ore_errors() {
+        let err = TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(OreError::NeedsReset as u32),
+        );
+        let decoded = decode_transaction_error(&err, &PROGRAM_IDS);
+        assert_eq!(decoded.instruction, Some(2));
+        assert_eq!(decoded.program, "ORE");
+        assert_eq!(decoded.name, "NeedsReset");
+        assert!(decoded.is_retryable());
+        assert!(decoded.is_needs_reset());
+
+        let err = TransactionError::InstructionError(2, InstructionError::Custom(2));
+        let decoded = decode_transaction_error(&err, &PROGRAM_IDS);
+        assert_eq!(decoded.name, "HashTooEasy");
+        assert_eq!(decoded.class, ErrorClass::Fatal);
+    }
+
+    #[test]
+    fn custom_codes_of_other_programs_are_not_ore_errors() {
+        let err = TransactionError::InstructionError(0, InstructionError::Custom(2));
+        let decoded = decode_transaction_error(&err, &PROGRAM_IDS);
+        assert_eq!(decoded.program, "Compute Budget");
+        assert_eq!(decoded.code, Some(2));
+        assert_eq!(decoded.class, ErrorClass::Fatal);
+        assert!(!decoded.is_needs_reset());
+    }
+
+    #[test]
+    fn decodes_pool_and_boost_errors_by_program_id() {
+        let program_ids = [ore_pool_api::ID, eore_boost_api::ID];
+        let cases = [
+            (
+                0,
+                PoolError::MissingMiningReward as u32,
+                "Pool",
+                "MissingMiningReward",
+                ErrorClass::Fatal,
+            ),
+            (
+                0,
+                PoolError::WithdrawOnlyMode as u32,
+                "Pool",
+                "WithdrawOnlyMode",
+                ErrorClass::Fatal,
+            ),
+            (
+                0,
+                PoolError::AttributionTooLarge as u32,
+                "Pool",
+                "AttributionTooLarge",
+                ErrorClass::Retryable,
+            ),
+            (
+                1,
+                BoostError::Dummy as u32,
+                "Boost",
+                "Dummy",
+                ErrorClass::Fatal,
+            ),
+        ];
+        for (index, code, program, name, class) in cases {
+            let err = TransactionError::InstructionError(index, InstructionError::Custom(code));
+            let decoded = decode_transaction_error(&err, &program_ids);
+            assert_eq!(decoded.program, program);
+            assert_eq!(decoded.name, name);
+            assert_eq!(decoded.class, class);
+        }
+
+        // The same code means something else to each program
+        let err = TransactionError::InstructionError(1, InstructionError::Custom(3));
+        let decoded = decode_transaction_error(&err, &program_ids);
+        assert_eq!(decoded.explanation, "unrecognized custom program error");
+    }
+
+    #[test]
+    fn classifies_transaction_errors() {
+        let decoded = decode_transaction_error(&TransactionError::BlockhashNotFound, &[]);
+        assert_eq!(decoded.instruction, None);
+        assert!(decoded.is_retryable());
+
+        let decoded = decode_transaction_error(&TransactionError::InsufficientFundsForFee, &[]);
+        assert_eq!(decoded.class, ErrorClass::Fatal);
+    }
+
+    #[test]
+    fn unknown_instructions_are_attributed_to_a_program() {
+        let err = TransactionError::InstructionError(7, InstructionError::InvalidAccountData);
+        let decoded = decode_transaction_error(&err, &PROGRAM_IDS);
+        assert_eq!(decoded.instruction, Some(7));
+        assert_eq!(decoded.program, "Program");
+        assert_eq!(decoded.class, ErrorClass::Fatal);
+    }
+}
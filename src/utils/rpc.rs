@@ -6,7 +6,7 @@ use eore_api::{
     state::{proof_pda, Bus, Config, Proof, Treasury},
 };
 use eore_boost_api::state::{Boost, Stake};
-use ore_pool_api::state::{Member, Pool};
+use ore_pool_api::state::{Member, Pool, Share};
 use serde::Deserialize;
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::nonblocking::rpc_client::RpcClient;
@@ -15,8 +15,18 @@ use solana_client::{
     rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
     rpc_filter::{Memcmp, RpcFilterType},
 };
-use solana_program::{program_pack::Pack, pubkey::Pubkey, sysvar};
-use solana_sdk::{clock::Clock, hash::Hash, clock::UnixTimestamp};
+use solana_program::{
+    address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{
+    clock::Clock,
+    clock::UnixTimestamp,
+    hash::Hash,
+    nonce::{self, state::Versions},
+};
 use spl_token::state::Mint;
 use steel::{AccountDeserialize, Discriminator};
 use tokio::time::sleep;
@@ -25,7 +35,7 @@ pub const BLOCKHASH_QUERY_RETRIES: usize = 5;
 pub const BLOCKHASH_QUERY_DELAY: u64 = 500;
 
 pub enum ComputeBudget {
-    #[allow(dead_code)]
+    /// Sized by simulating the transaction.
     Dynamic,
     Fixed(u32),
 }
@@ -138,6 +148,11 @@ pub async fn get_member(client: &RpcClient, address: Pubkey) -> Result<Member, a
     Ok(*Member::try_from_bytes(&data)?)
 }
 
+pub async fn get_share(client: &RpcClient, address: Pubkey) -> Result<Share, anyhow::Error> {
+    let data = client.get_account_data(&address).await?;
+    Ok(*Share::try_from_bytes(&data)?)
+}
+
 pub async fn get_stake(client: &RpcClient, address: Pubkey) -> Result<Stake, anyhow::Error> {
     let data = client.get_account_data(&address).await?;
     Ok(*Stake::try_from_bytes(&data)?)
@@ -184,6 +199,33 @@ pub async fn get_proof(client: &RpcClient, address: Pubkey) -> Result<Proof, any
     Ok(*Proof::try_from_bytes(&data)?)
 }
 
+/// Fetches an address lookup table with the addresses it holds.
+pub async fn get_lookup_table(
+    client: &RpcClient,
+    address: Pubkey,
+) -> Result<AddressLookupTableAccount, anyhow::Error> {
+    let data = client.get_account_data(&address).await?;
+    let lookup_table = AddressLookupTable::deserialize(&data)?;
+    Ok(AddressLookupTableAccount {
+        key: address,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+/// Fetches an initialized durable nonce account.
+pub async fn get_nonce(
+    client: &RpcClient,
+    address: Pubkey,
+) -> Result<nonce::state::Data, anyhow::Error> {
+    let data = client.get_account_data(&address).await?;
+    match bincode::deserialize::<Versions>(&data)?.state() {
+        nonce::State::Initialized(data) => Ok(data.clone()),
+        nonce::State::Uninitialized => {
+            Err(anyhow::anyhow!("nonce account {} is not initialized", address))
+        }
+    }
+}
+
 pub async fn get_clock(client: &RpcClient) -> Result<Clock, anyhow::Error> {
     retry(|| async {
         let data = client.get_account_data(&sysvar::clock::ID).await?;
@@ -74,6 +74,14 @@ impl SoloCollectingData {
             status: "Failed".bold().red().to_string(),
         }
     }
+
+    /// A landed transaction whose event could not be fetched.
+    pub fn unfetched(sig: Signature) -> Self {
+        Self {
+            status: "Unknown".bold().yellow().to_string(),
+            ..Self::fetching(sig)
+        }
+    }
 }
 
 